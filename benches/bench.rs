@@ -1,5 +1,3 @@
-#![feature(test)]
-
 #[macro_use]
 extern crate criterion;
 use criterion::Criterion;
@@ -41,11 +39,8 @@ fn simulate(file: &str) {
     .with_operating_system("LC3_OS.obj")
     .load(file);
 
-    match simulator {
-        Ok(sim) => {
-            sim.execute();
-        }
-        _ => {}
+    if let Ok(mut sim) = simulator {
+        sim.execute();
     }
 }
 
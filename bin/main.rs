@@ -1,12 +1,22 @@
 extern crate clap;
 extern crate crossterm;
 
+use std::fs::File;
+use std::io::{self, BufReader};
 use std::iter::Iterator;
 
 use clap::{App, Arg};
 
+use lc3simlib::debugger::Debugger;
 use lc3simlib::simulator;
-use simulator::{Reader, Simulator, Tracer, Writer};
+use lc3simlib::split_view::{SplitView, MINIMUM_COLUMNS, MINIMUM_OUTPUT_ROWS};
+use simulator::{
+    CacheConfig, ConditionCode, DdrRead, DeviceLatency, EofPolicy, Formatter, PredictorKind,
+    Reader, Simulator, TraceFormat, Tracer, Writer,
+};
+
+/// How many lines of the trace the split view shows
+const SPLIT_VIEW_TRACE_LINES: u16 = 8;
 
 fn valid_instruction(instr: String) -> Result<(), String> {
     match instr.to_ascii_uppercase().as_ref() {
@@ -16,6 +26,132 @@ fn valid_instruction(instr: String) -> Result<(), String> {
     }
 }
 
+fn parse_number(address: &str) -> Result<u16, String> {
+    address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix('x'))
+        .map_or_else(
+            || address.parse().ok(),
+            |hex| u16::from_str_radix(hex, 16).ok(),
+        )
+        .ok_or_else(|| String::from("Expected a number (e.g. x3000)"))
+}
+
+fn valid_address(address: String) -> Result<(), String> {
+    parse_number(&address).map(|_| ())
+}
+
+fn valid_interval(interval: String) -> Result<(), String> {
+    interval
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| String::from("Expected a number of instructions (0 to disable)"))
+}
+
+fn valid_seed(seed: String) -> Result<(), String> {
+    seed.parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| String::from("Expected a number to use as the seed"))
+}
+
+fn valid_cycles(cycles: String) -> Result<(), String> {
+    cycles
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| String::from("Expected a number of cycles"))
+}
+
+fn valid_history_bits(bits: String) -> Result<(), String> {
+    bits.parse::<u32>()
+        .ok()
+        .filter(|&bits| bits <= 16)
+        .map(|_| ())
+        .ok_or_else(|| String::from("Expected a number of bits of history (0 to 16)"))
+}
+
+fn parse_register(register: &str) -> Result<usize, String> {
+    register
+        .trim_start_matches(['R', 'r'])
+        .parse()
+        .ok()
+        .filter(|&register| register < 8)
+        .ok_or_else(|| String::from("Expected a register (R0 to R7)"))
+}
+
+fn valid_register(register: String) -> Result<(), String> {
+    parse_register(&register).map(|_| ())
+}
+
+fn parse_cache(cache: &[&str]) -> Result<CacheConfig, String> {
+    let sizes = cache
+        .iter()
+        .map(|size| size.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| String::from("Expected the cache's size, line size, and associativity"))?;
+
+    match sizes.as_slice() {
+        &[size, line_size, associativity]
+            if line_size * associativity != 0
+                && size >= line_size * associativity
+                && size.is_multiple_of(line_size * associativity) =>
+        {
+            Ok(CacheConfig {
+                size,
+                line_size,
+                associativity,
+                ..CacheConfig::default()
+            })
+        }
+        _ => Err(String::from(
+            "Expected the cache's size to be a whole number of sets of lines",
+        )),
+    }
+}
+
+fn parse_range(range: &str) -> Result<(u16, u16), String> {
+    let mut addresses = range.splitn(2, '-').map(parse_number);
+
+    match (addresses.next(), addresses.next()) {
+        (Some(Ok(start)), Some(Ok(end))) if start <= end => Ok((start, end)),
+        _ => Err(String::from(
+            "Expected a range of addresses (e.g. x3000-x30FF)",
+        )),
+    }
+}
+
+fn valid_range(range: String) -> Result<(), String> {
+    parse_range(&range).map(|_| ())
+}
+
+fn parse_eof_policy(policy: &str) -> Result<EofPolicy, String> {
+    match policy {
+        "halt" => Ok(EofPolicy::Halt),
+        "block" => Ok(EofPolicy::Block),
+        byte => parse_number(byte)
+            .ok()
+            .filter(|&byte| byte <= 0xFF)
+            .map(|byte| EofPolicy::Sentinel(byte as u8))
+            .ok_or_else(|| String::from("Expected halt, block, or a byte to use as a sentinel")),
+    }
+}
+
+fn valid_eof_policy(policy: String) -> Result<(), String> {
+    parse_eof_policy(&policy).map(|_| ())
+}
+
+fn parse_ddr_read(ddr_read: &str) -> Result<DdrRead, String> {
+    match ddr_read {
+        "last" => Ok(DdrRead::LastWritten),
+        value => parse_number(value)
+            .map(DdrRead::Fixed)
+            .map_err(|_| String::from("Expected last, or a value to read (e.g. x0000)")),
+    }
+}
+
+fn valid_ddr_read(ddr_read: String) -> Result<(), String> {
+    parse_ddr_read(&ddr_read).map(|_| ())
+}
+
 fn main() {
     let args = App::new("lc3sim")
         .arg(Arg::with_name("file").required(true))
@@ -26,6 +162,18 @@ fn main() {
                 .help("The output file (for writing to)")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("line-buffered")
+                .long("line-buffered")
+                .help("Flush the output file on every newline, so it can be watched as it's written")
+                .requires("output"),
+        )
+        .arg(
+            Arg::with_name("sync-output")
+                .long("sync-output")
+                .help("Sync the output file to disk on every newline as well")
+                .requires("line-buffered"),
+        )
         .arg(
             Arg::with_name("input")
                 .long("input")
@@ -33,6 +181,99 @@ fn main() {
                 .help("The input file (for reading from)")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("output-binary")
+                .long("output-binary")
+                .help("Write the program's output as raw bytes, without adding carriage returns before newlines"),
+        )
+        .arg(
+            Arg::with_name("output-on-clean-halt")
+                .long("output-on-clean-halt")
+                .help("Only show the program's output if it halts cleanly, rather than on an error"),
+        )
+        .arg(
+            Arg::with_name("record-input")
+                .long("record-input")
+                .help("Record everything typed at the keyboard to this file")
+                .takes_value(true)
+                .conflicts_with("input"),
+        )
+        .arg(
+            Arg::with_name("replay-input")
+                .long("replay-input")
+                .help("Replay input previously recorded with --record-input")
+                .takes_value(true)
+                .conflicts_with_all(&["input", "record-input"]),
+        )
+        .arg(
+            Arg::with_name("then-keyboard")
+                .long("then-keyboard")
+                .help("Once the input file runs out, take input from the keyboard instead"),
+        )
+        .arg(
+            Arg::with_name("eof")
+                .long("eof")
+                .help("What to do when the input runs out: halt, block (which halts anyway for a file), or a byte to keep reading (e.g. x04)")
+                .takes_value(true)
+                .default_value("halt")
+                .validator(valid_eof_policy),
+        )
+        .arg(
+            Arg::with_name("ddr-read")
+                .long("ddr-read")
+                .help("What reading the DDR gives: last (the last value written), or a value (e.g. x0000)")
+                .takes_value(true)
+                .default_value("x0000")
+                .validator(valid_ddr_read),
+        )
+        .arg(
+            Arg::with_name("interrupt-check")
+                .long("interrupt-check")
+                .help("How often (in instructions) to check whether ESC was pressed (0 to disable)")
+                .takes_value(true)
+                .default_value("1000")
+                .validator(valid_interval),
+        )
+        .arg(
+            Arg::with_name("initial-cc")
+                .long("initial-cc")
+                .help("The condition code to start with")
+                .takes_value(true)
+                .possible_values(&["N", "Z", "P"])
+                .case_insensitive(true)
+                .default_value("Z"),
+        )
+        .arg(
+            Arg::with_name("randomize-registers")
+                .long("randomize-registers")
+                .help("Start with the registers filled with pseudo-random values from this seed")
+                .takes_value(true)
+                .validator(valid_seed),
+        )
+        .arg(
+            Arg::with_name("diagnostics")
+                .long("diagnostics")
+                .help("Write any warnings and faults to this file as JSON, for editors to show")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("call-graph")
+                .long("call-graph")
+                .help("Write which subroutines called which to this file, as a Graphviz graph")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("listing-executed")
+                .long("listing-executed")
+                .help("Write a disassembly of only the instructions which were executed, with how many times each was, to this file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("access-heatmap")
+                .long("access-heatmap")
+                .help("Write a text heatmap of how often each address was accessed to this file")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("trace")
                 .long("trace")
@@ -49,12 +290,230 @@ fn main() {
                 .number_of_values(1)
                 .validator(valid_instruction),
         )
+        .arg(
+            Arg::with_name("trace-format")
+                .long("trace-format")
+                .help("How to format each traced instruction")
+                .takes_value(true)
+                .possible_values(&["full", "pretty", "json", "csv"])
+                .default_value("full"),
+        )
+        .arg(
+            Arg::with_name("trace-gate")
+                .long("trace-gate")
+                .help("Only trace while the word at this address is nonzero")
+                .takes_value(true)
+                .validator(valid_address),
+        )
+        .arg(
+            Arg::with_name("trace-memory")
+                .long("trace-memory")
+                .help("Trace every read and store an instruction makes within this range (e.g. x3000-x30FF)")
+                .takes_value(true)
+                .validator(valid_range),
+        )
+        .arg(
+            Arg::with_name("trace-prediction")
+                .long("trace-prediction")
+                .help("Trace what the branch predictor guessed for each conditional branch")
+                .requires("trace"),
+        )
+        .arg(
+            Arg::with_name("split-view")
+                .long("split-view")
+                .help("Show the program's output above a live trace of the last few instructions")
+                .conflicts_with_all(&["trace", "output"]),
+        )
+        .arg(
+            Arg::with_name("predictor")
+                .long("predictor")
+                .help("Which branch predictor to use")
+                .takes_value(true)
+                .possible_values(&["static", "bimodal", "gshare"])
+                .default_value("bimodal"),
+        )
+        .arg(
+            Arg::with_name("history-bits")
+                .long("history-bits")
+                .help("How many branches of global history the gshare predictor keeps (up to 16)")
+                .takes_value(true)
+                .default_value("8")
+                .validator(valid_history_bits),
+        )
+        .arg(
+            Arg::with_name("prediction-report")
+                .long("prediction-report")
+                .help("After running, show how often each conditional branch was mispredicted"),
+        )
+        .arg(
+            Arg::with_name("hotspots")
+                .long("hotspots")
+                .help("After running, show this many of the most executed instructions")
+                .takes_value(true)
+                .validator(valid_interval),
+        )
+        .arg(
+            Arg::with_name("cache")
+                .long("cache")
+                .help("Simulate a data cache, showing how it performed after running")
+                .takes_value(true)
+                .number_of_values(3)
+                .value_names(&["size", "line-size", "associativity"]),
+        )
+        .arg(
+            Arg::with_name("device-latency")
+                .long("device-latency")
+                .help("Stall for this many cycles each time the KBSR or DSR is polled, showing the cycle count after running")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["keyboard", "display"])
+                .validator(valid_cycles),
+        )
+        .arg(
+            Arg::with_name("trace-ring")
+                .long("trace-ring")
+                .help("Keep the last N instructions traced in memory, and show them if the simulator stops because of an error")
+                .takes_value(true)
+                .validator(valid_interval),
+        )
+        .arg(
+            Arg::with_name("hex-prefix")
+                .long("hex-prefix")
+                .help("The prefix to write hex numbers with (e.g. 0x)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("lowercase-hex")
+                .long("lowercase-hex")
+                .help("Write hex numbers in lowercase"),
+        )
         .arg(
             Arg::with_name("user")
                 .long("user-only")
                 .short("u")
                 .help("Only trace user space instructions (instructions at addresses >= 0x3000)"),
         )
+        .arg(
+            Arg::with_name("echo")
+                .long("echo")
+                .short("e")
+                .help("Echo characters typed at the keyboard to the output"),
+        )
+        .arg(
+            Arg::with_name("debug-script")
+                .long("debug-script")
+                .help("Run the program under the debugger, using the commands in this file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("quiet-halt")
+                .long("quiet-halt")
+                .help("Don't show anything printed by the operating system's HALT routine"),
+        )
+        .arg(
+            Arg::with_name("decimal-trap")
+                .long("decimal-trap")
+                .help("Handle TRAP x31 by printing R0 as a signed decimal number"),
+        )
+        .arg(
+            Arg::with_name("check-encodings")
+                .long("check-encodings")
+                .help("Warn about loaded instructions with unused bits set"),
+        )
+        .arg(
+            Arg::with_name("strict-decoding")
+                .long("strict-decoding")
+                .help("Warn about executed register mode ADD and AND instructions with bits 4 and 3 set"),
+        )
+        .arg(
+            Arg::with_name("check-next-store")
+                .long("check-next-store")
+                .help("Stop when a store overwrites the next instruction to execute"),
+        )
+        .arg(
+            Arg::with_name("check-trap-table")
+                .long("check-trap-table")
+                .help("Stop when user code stores into the trap vector table"),
+        )
+        .arg(
+            Arg::with_name("check-devices")
+                .long("check-devices")
+                .help("Warn about writes to read only device registers, and reads from write only ones"),
+        )
+        .arg(
+            Arg::with_name("check-null-execution")
+                .long("check-null-execution")
+                .help("Stop when user code jumps into the trap vector table"),
+        )
+        .arg(
+            Arg::with_name("break-on-write")
+                .long("break-on-write")
+                .help("Stop when user code first writes to this register (e.g. R5)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(valid_register),
+        )
+        .arg(
+            Arg::with_name("check-returns")
+                .long("check-returns")
+                .help("Warn when a RET doesn't return to the address saved by its matching call"),
+        )
+        .arg(
+            Arg::with_name("dump-memory-file")
+                .long("dump-memory-file")
+                .help("After running, save a range of memory (e.g. x3000-x30FF) as an object file")
+                .takes_value(true)
+                .number_of_values(2)
+                .value_names(&["file", "range"]),
+        )
+        .arg(
+            Arg::with_name("stack-guard")
+                .long("stack-guard")
+                .help("Warn about LDR and STR relative to the stack pointer outside this range (e.g. x4000-x4FFF)")
+                .takes_value(true)
+                .validator(valid_range),
+        )
+        .arg(
+            Arg::with_name("stack-pointer")
+                .long("stack-pointer")
+                .help("The register the stack guard treats as the stack pointer")
+                .takes_value(true)
+                .default_value("R6")
+                .validator(valid_register),
+        )
+        .arg(
+            Arg::with_name("export-replay")
+                .long("export-replay")
+                .help("After running, save the executed instructions as a straight line program replaying the run")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("metadata")
+                .long("metadata")
+                .help("A JSON file marking regions of memory as code, data, or stack")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("symbols")
+                .long("symbols")
+                .help("A symbol table (such as the .sym file from lc3as) for labelling the trace")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trap-base")
+                .long("trap-base")
+                .help("Where the operating system's trap vector table starts")
+                .takes_value(true)
+                .default_value("x0000")
+                .validator(valid_address),
+        )
+        .arg(
+            Arg::with_name("builtin-traps")
+                .long("builtin-traps")
+                .help("Handle the standard traps in the simulator, instead of loading an operating system")
+                .conflicts_with("os"),
+        )
         .arg(
             Arg::with_name("os")
                 .long("os")
@@ -73,34 +532,368 @@ fn main() {
         )
         .get_matches();
 
+    let dump = match args
+        .values_of("dump-memory-file")
+        .map(Iterator::collect::<Vec<_>>)
+    {
+        Some(dump) => match parse_range(dump[1]) {
+            Ok(range) => Some((dump[0], range)),
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let (input, output) = match simulator::open_files(
+        args.value_of("input")
+            .or_else(|| args.value_of("replay-input")),
+        args.value_of("output"),
+    ) {
+        Ok((input, output)) if args.is_present("line-buffered") => {
+            (input, output.line_buffered(args.is_present("sync-output")))
+        }
+        Ok(files) => files,
+        Err(e) => {
+            println!("Error: {}", e);
+            return;
+        }
+    };
+    let input = if args.is_present("then-keyboard")
+        && (args.is_present("input") || args.is_present("replay-input"))
+    {
+        input.then(Reader::default())
+    } else {
+        input
+    };
+    let input = match args.value_of("record-input") {
+        Some(recording) => match input.record(recording) {
+            Ok(recorder) => recorder,
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        },
+        None => input,
+    };
+
+    // The split view needs room for its trace lines, and a few lines of output, otherwise
+    // the program runs as though it wasn't asked for
+    let split_view = if args.is_present("split-view") {
+        let view = SplitView::new(SPLIT_VIEW_TRACE_LINES);
+        if view.is_none() {
+            eprintln!(
+                "Warning: The terminal must be at least {} columns by {} rows for the split view",
+                MINIMUM_COLUMNS,
+                SPLIT_VIEW_TRACE_LINES + 1 + MINIMUM_OUTPUT_ROWS
+            );
+        }
+        view
+    } else {
+        None
+    };
+
+    let (writer, tracer) = match split_view {
+        Some(ref view) => (
+            Writer::Custom(Box::new(view.output())),
+            Tracer::Custom(Box::new(view.trace())),
+        ),
+        None => (
+            output,
+            Tracer::from((
+                args.value_of("trace"),
+                args.values_of("instr").map(Iterator::collect),
+                args.is_present("user"),
+            )),
+        ),
+    };
+
+    let tracer = match args.value_of("trace-format") {
+        Some("json") => tracer.json(),
+        Some("csv") => tracer.csv(),
+        _ => tracer,
+    };
+
+    let simulator = Simulator::new(input, writer, tracer)
+        .with_trap_base(parse_number(args.value_of("trap-base").unwrap()).unwrap());
+
+    let simulator = if args.is_present("builtin-traps") {
+        simulator.with_builtin_traps(true)
+    } else {
+        simulator.with_operating_system(args.value_of("os").unwrap())
+    };
+
+    let simulator = simulator
+        .with_echo(args.is_present("echo"))
+        .with_binary_output(args.is_present("output-binary"))
+        .with_output_on_clean_halt(args.is_present("output-on-clean-halt"))
+        .with_eof_policy(parse_eof_policy(args.value_of("eof").unwrap()).unwrap())
+        .with_ddr_read(parse_ddr_read(args.value_of("ddr-read").unwrap()).unwrap())
+        .with_interrupt_check(args.value_of("interrupt-check").unwrap().parse().unwrap())
+        .with_trace_format(match args.value_of("trace-format") {
+            _ if split_view.is_some() => TraceFormat::Pretty,
+            Some("pretty") => TraceFormat::Pretty,
+            _ => TraceFormat::Full,
+        })
+        .with_prediction_trace(args.is_present("trace-prediction"))
+        .with_predictor_kind(match args.value_of("predictor") {
+            Some("static") => PredictorKind::Static,
+            Some("gshare") => PredictorKind::GShare {
+                history_bits: args.value_of("history-bits").unwrap().parse().unwrap(),
+            },
+            _ => PredictorKind::Bimodal,
+        })
+        .with_return_check(args.is_present("check-returns"))
+        .with_encoding_check(args.is_present("check-encodings"))
+        .with_strict_decoding(args.is_present("strict-decoding"))
+        .with_next_store_check(args.is_present("check-next-store"))
+        .with_trap_table_check(args.is_present("check-trap-table"))
+        .with_null_execution_check(args.is_present("check-null-execution"))
+        .with_device_check(args.is_present("check-devices"))
+        .with_access_counting(args.is_present("access-heatmap"))
+        .with_execution_recording(args.is_present("export-replay"))
+        .with_condition_code(match args.value_of("initial-cc") {
+            Some("N") | Some("n") => ConditionCode::Negative,
+            Some("P") | Some("p") => ConditionCode::Positive,
+            _ => ConditionCode::Zero,
+        })
+        .with_quiet_halt(args.is_present("quiet-halt"))
+        .with_decimal_trap(args.is_present("decimal-trap"));
+
+    let simulator = match args.value_of("trace-gate") {
+        Some(gate) => simulator.with_trace_gate(parse_number(gate).unwrap()),
+        None => simulator,
+    };
+
+    let simulator = match args.value_of("trace-memory") {
+        Some(range) => {
+            let (start, end) = parse_range(range).unwrap();
+            simulator.with_memory_trace(start, end)
+        }
+        None => simulator,
+    };
+
+    let simulator = match args.value_of("stack-guard") {
+        Some(range) => {
+            let (start, end) = parse_range(range).unwrap();
+            simulator.with_stack_guard(start, end).with_stack_pointer(
+                parse_register(args.value_of("stack-pointer").unwrap()).unwrap(),
+            )
+        }
+        None => simulator,
+    };
+
+    let simulator = match args.value_of("randomize-registers") {
+        Some(seed) => simulator.with_randomized_registers(seed.parse().unwrap()),
+        None => simulator,
+    };
+
+    let formatter = Formatter::default().with_lowercase(args.is_present("lowercase-hex"));
+    let simulator = simulator.with_formatter(match args.value_of("hex-prefix") {
+        Some(prefix) => formatter.with_prefix(prefix),
+        None => formatter,
+    });
+
+    let simulator = match args.values_of("cache").map(Iterator::collect::<Vec<_>>) {
+        Some(cache) => match parse_cache(&cache) {
+            Ok(config) => simulator.with_cache(config),
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        },
+        None => simulator,
+    };
+
+    let simulator = match args
+        .values_of("device-latency")
+        .map(Iterator::collect::<Vec<_>>)
+    {
+        Some(latency) => simulator.with_device_latency(DeviceLatency {
+            keyboard: latency[0].parse().unwrap(),
+            display: latency[1].parse().unwrap(),
+        }),
+        None => simulator,
+    };
+
+    let simulator = match args.value_of("trace-ring") {
+        Some(size) => simulator.with_trace_ring(size.parse().unwrap()),
+        None => simulator,
+    };
+
+    let simulator = match args.value_of("metadata") {
+        Some(metadata) => match simulator.with_metadata(metadata) {
+            Ok(simulator) => simulator,
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        },
+        None => simulator,
+    };
+
+    let simulator = match args.value_of("symbols") {
+        Some(symbols) => match simulator.load_symbols(symbols) {
+            Ok(simulator) => simulator,
+            Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        },
+        None => simulator,
+    };
+
     let simulator = args
         .values_of("data")
         .map(Iterator::collect::<Vec<_>>)
         .unwrap_or_default()
         .iter()
-        .fold(
-            Simulator::new(
-                Reader::from(args.value_of("input")),
-                Writer::from(args.value_of("output")),
-                Tracer::from((
-                    args.value_of("trace"),
-                    args.values_of("instr").map(Iterator::collect),
-                    args.is_present("user"),
-                )),
-            )
-            .with_operating_system(args.value_of("os").unwrap()),
-            |sim, data| match sim.load(data) {
-                Ok(simulator) => simulator,
-                Err(e) => {
-                    println!("Error: {}", e);
-                    panic!();
+        .fold(simulator, |sim, data| match sim.load(data) {
+            Ok(simulator) => simulator,
+            Err(e) => {
+                println!("Error: {}", e);
+                panic!();
+            }
+        });
+
+    let file = args.value_of("file").unwrap();
+    let loaded = if file.ends_with(".hex") {
+        simulator.load_hex(file)
+    } else {
+        simulator.load(file)
+    };
+
+    match loaded {
+        Ok(mut simulator) => {
+            args.values_of("break-on-write")
+                .map(Iterator::collect::<Vec<_>>)
+                .unwrap_or_default()
+                .iter()
+                .for_each(|register| {
+                    simulator.break_on_register_write(parse_register(register).unwrap())
+                });
+
+            match args.value_of("debug-script") {
+                Some(script) => {
+                    if let Err(e) = File::open(script).and_then(|script| {
+                        Debugger::new(simulator, io::stdout()).run_script(BufReader::new(script))
+                    }) {
+                        println!("Error: {}", e);
+                    }
                 }
-            },
-        );
+                None => {
+                    simulator.execute();
+                    if let Some(view) = split_view {
+                        if let Err(e) = view.draw() {
+                            println!("Error: {}", e);
+                        }
+                    }
+                    simulator
+                        .warnings()
+                        .iter()
+                        .for_each(|warning| eprintln!("\r\nWarning: {}\r", warning));
+                    simulator
+                        .crash_trace()
+                        .iter()
+                        .for_each(|trace| eprintln!("{}\r", trace));
+
+                    if let Some(stats) = simulator.cache_stats() {
+                        println!(
+                            "Cache: {} hits, {} misses ({:.1}% hit rate), {} stall cycles\r",
+                            stats.hits,
+                            stats.misses,
+                            stats.hit_rate() * 100.0,
+                            stats.stall_cycles
+                        );
+                    }
+
+                    if args.is_present("device-latency") {
+                        println!(
+                            "Cycles: {} ({} waiting on devices)\r",
+                            simulator.cycles(),
+                            simulator.io_wait_cycles()
+                        );
+                    }
+
+                    if args.is_present("prediction-report") {
+                        simulator.misprediction_report().iter().for_each(
+                            |(address, total, mispredicted)| {
+                                println!(
+                                    "Branch at {}: {} of {} mispredicted\r",
+                                    simulator.formatter().hex(*address),
+                                    mispredicted,
+                                    total
+                                )
+                            },
+                        );
+
+                        let stats = simulator.prediction_stats();
+                        println!(
+                            "Branch prediction: {} of {} correct ({:.1}% accuracy)\r",
+                            stats.correct,
+                            stats.total,
+                            stats.accuracy() * 100.0
+                        );
+                    }
+
+                    if let Some(count) = args.value_of("hotspots") {
+                        let mut hotspots =
+                            simulator.execution_counts().into_iter().collect::<Vec<_>>();
+                        hotspots.sort_unstable_by(|(a, a_count), (b, b_count)| {
+                            b_count.cmp(a_count).then(a.cmp(b))
+                        });
 
-    match simulator.load(args.value_of("file").unwrap()) {
-        Ok(simulator) => {
-            simulator.execute();
+                        hotspots.iter().take(count.parse().unwrap()).for_each(
+                            |(address, executed)| {
+                                println!(
+                                    "{}  {}: executed {} times\r",
+                                    simulator.formatter().hex(*address),
+                                    simulator.disassemble(*address),
+                                    executed
+                                )
+                            },
+                        );
+                    }
+
+                    if let Some(file) = args.value_of("export-replay") {
+                        let replay = simulator.export_replay(simulator.entry_point());
+                        if let Err(e) = std::fs::write(file, replay) {
+                            println!("Error: {}", e);
+                        }
+                    }
+
+                    if let Some(file) = args.value_of("diagnostics") {
+                        if let Err(e) = std::fs::write(file, simulator.diagnostics_json()) {
+                            println!("Error: {}", e);
+                        }
+                    }
+
+                    if let Some(file) = args.value_of("call-graph") {
+                        if let Err(e) = std::fs::write(file, simulator.call_graph_dot()) {
+                            println!("Error: {}", e);
+                        }
+                    }
+
+                    if let Some(file) = args.value_of("listing-executed") {
+                        if let Err(e) = std::fs::write(file, simulator.executed_listing()) {
+                            println!("Error: {}", e);
+                        }
+                    }
+
+                    if let Some(file) = args.value_of("access-heatmap") {
+                        if let Err(e) = std::fs::write(file, simulator.access_heatmap()) {
+                            println!("Error: {}", e);
+                        }
+                    }
+
+                    if let Some((file, (start, end))) = dump {
+                        if let Err(e) = std::fs::write(file, simulator.export_obj(start, end)) {
+                            println!("Error: {}", e);
+                        }
+                    }
+                }
+            }
         }
         Err(e) => println!("Error: {}", e),
     };
@@ -0,0 +1,316 @@
+use std::io::{BufRead, Error, Write};
+
+use crate::simulator::{BreakpointKind, Simulator, WatchKind};
+
+/// A simple command driven debugger wrapped around a simulator.
+///
+/// The supported commands are:
+///  - `break <address>` to stop before the instruction at `address` executes
+///  - `watch <start> <end>` to stop after anything is stored between `start` and `end`
+///  - `info break` to list the breakpoints and watchpoints, along with their ids
+///  - `delete [id]` to remove the breakpoint or watchpoint with `id`, or all of them
+///  - `continue` to run until the next breakpoint or watchpoint, or until the machine halts
+///  - `step [count]` to execute `count` (default 1) instructions
+///  - `regs` to show the registers, PC, IR, and condition code
+///  - `find <value>...` to show where the sequence of values appears in memory, or
+///    `find all <value>...` to search the device registers as well
+///  - `quit` to stop debugging
+///
+/// Addresses may be written as `x3000`, `0x3000`, or as a decimal number.
+pub struct Debugger<W: Write> {
+    simulator: Simulator,
+    output: W,
+}
+
+impl<W: Write> Debugger<W> {
+    #[must_use]
+    pub fn new(simulator: Simulator, output: W) -> Self {
+        Self { simulator, output }
+    }
+
+    /// The simulator being debugged
+    #[must_use]
+    pub fn simulator(&self) -> &Simulator {
+        &self.simulator
+    }
+
+    /// The output the debugger is writing its results to
+    #[must_use]
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+
+    /// Run every command in the script, stopping early on a `quit` command.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::debugger::Debugger;
+    /// use lc3simlib::simulator::Simulator;
+    /// use std::io::Cursor;
+    ///
+    /// let simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #1
+    ///              ADD R1, R1, #1
+    ///              ADD R1, R1, #1
+    ///              STI R2, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// let mut debugger = Debugger::new(simulator, Vec::new());
+    /// debugger
+    ///     .run_script(Cursor::new("break x3002\ncontinue\nregs\ncontinue\n"))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8_lossy(debugger.output()),
+    ///     "Breakpoint reached at 0x3002\n\
+    ///      PC: 0x3002 IR: 0x1261 CC: P\n\
+    ///      R0: 0x0000 R1: 0x0002 R2: 0x0000 R3: 0x0000\n\
+    ///      R4: 0x0000 R5: 0x0000 R6: 0x0000 R7: 0x0000\n\
+    ///      Machine halted\n"
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the script couldn't be read, or the results couldn't be written
+    pub fn run_script<R: BufRead>(&mut self, script: R) -> Result<(), Error> {
+        for line in script.lines() {
+            if !self.command(&line?)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run a single command, returning whether debugging should continue.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::debugger::Debugger;
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let mut simulator = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace);
+    /// simulator.write(0x3000, 0x0048);
+    /// simulator.write(0x3001, 0x0069);
+    ///
+    /// let mut debugger = Debugger::new(simulator, Vec::new());
+    /// for command in ["find x48 x69", "find x69 x48", "find x8000", "find all x8000"] {
+    ///     assert!(debugger.command(command).unwrap());
+    /// }
+    ///
+    /// // The DSR and MCR both hold x8000 while the display is ready and the clock is running
+    /// assert_eq!(
+    ///     String::from_utf8_lossy(debugger.output()),
+    ///     "Found at 0x3000\n\
+    ///      Not found\n\
+    ///      Not found\n\
+    ///      Found at 0xFE04, 0xFFFE\n"
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the results of the command couldn't be written
+    pub fn command(&mut self, command: &str) -> Result<bool, Error> {
+        match command.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] => {}
+            ["break", address] => match parse_number(address) {
+                Some(address) => self.simulator.add_breakpoint(address),
+                None => writeln!(self.output, "Invalid address: {}", address)?,
+            },
+            ["watch", start, end] => match (parse_number(start), parse_number(end)) {
+                (Some(start), Some(end)) => self.simulator.add_region_watchpoint(start, end),
+                _ => writeln!(self.output, "Invalid range: {} {}", start, end)?,
+            },
+            ["info", "break"] => self.list_breakpoints()?,
+            ["delete"] => self.simulator.clear_breakpoints(),
+            ["delete", id] => match id.parse() {
+                Ok(id) if self.simulator.remove_breakpoint_by_id(id) => {}
+                _ => writeln!(self.output, "No breakpoint with id {}", id)?,
+            },
+            ["continue"] => {
+                self.simulator.execute();
+                self.report_stop()?;
+            }
+            ["step"] => self.step(1)?,
+            ["step", count] => match parse_number(count) {
+                Some(count) => self.step(count)?,
+                None => writeln!(self.output, "Invalid count: {}", count)?,
+            },
+            ["regs"] => self.registers()?,
+            ["find", values @ ..] if !values.is_empty() => {
+                let (include_devices, values) = match values {
+                    ["all", values @ ..] => (true, values),
+                    values => (false, values),
+                };
+
+                match values
+                    .iter()
+                    .map(|value| parse_number(value))
+                    .collect::<Option<Vec<_>>>()
+                {
+                    Some(ref pattern) if pattern.is_empty() => {
+                        writeln!(self.output, "Nothing to find")?
+                    }
+                    Some(pattern) => self.find(&pattern, include_devices)?,
+                    None => writeln!(self.output, "Invalid value in: {}", values.join(" "))?,
+                }
+            }
+            ["quit"] => return Ok(false),
+            _ => writeln!(self.output, "Unknown command: {}", command.trim())?,
+        }
+
+        Ok(true)
+    }
+
+    fn step(&mut self, count: u16) -> Result<(), Error> {
+        for _ in 0..count {
+            if !self.simulator.step().running || self.simulator.halt_reason().is_some() {
+                break;
+            }
+        }
+
+        self.report_stop()
+    }
+
+    fn list_breakpoints(&mut self) -> Result<(), Error> {
+        let breakpoints = self.simulator.breakpoints();
+        let formatter = self.simulator.formatter();
+
+        if breakpoints.is_empty() {
+            return writeln!(self.output, "No breakpoints");
+        }
+
+        for info in breakpoints {
+            match info.kind {
+                BreakpointKind::Address(address) => writeln!(
+                    self.output,
+                    "{}: break at {}",
+                    info.id,
+                    formatter.hex(address)
+                )?,
+                BreakpointKind::RegisterWrite(register) => {
+                    writeln!(self.output, "{}: break on writes to R{}", info.id, register)?
+                }
+                BreakpointKind::Watch(address, kind) => writeln!(
+                    self.output,
+                    "{}: watch {} of {}",
+                    info.id,
+                    match kind {
+                        WatchKind::Read => "reads",
+                        WatchKind::Write => "writes",
+                        WatchKind::ReadWrite => "reads and writes",
+                    },
+                    formatter.hex(address)
+                )?,
+                BreakpointKind::Region(start, end) => writeln!(
+                    self.output,
+                    "{}: watch {} to {}",
+                    info.id,
+                    formatter.hex(start),
+                    formatter.hex(end)
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn find(&mut self, pattern: &[u16], include_devices: bool) -> Result<(), Error> {
+        let addresses = self.simulator.find_pattern(pattern, include_devices);
+
+        if addresses.is_empty() {
+            writeln!(self.output, "Not found")
+        } else {
+            writeln!(
+                self.output,
+                "Found at {}",
+                addresses
+                    .iter()
+                    .map(|&address| self.simulator.formatter().hex(address))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    }
+
+    fn report_stop(&mut self) -> Result<(), Error> {
+        let formatter = self.simulator.formatter();
+
+        if !self.simulator.is_running() {
+            writeln!(self.output, "Machine halted")
+        } else if let Some(hit) = self.simulator.watch_hit() {
+            if hit.kind == WatchKind::Read {
+                return writeln!(
+                    self.output,
+                    "Watchpoint hit: {} read as {} by the instruction at {}",
+                    formatter.hex(hit.address),
+                    formatter.hex(hit.new),
+                    formatter.hex(hit.pc)
+                );
+            }
+            writeln!(
+                self.output,
+                "Watchpoint hit: {} changed from {} to {} by the instruction at {}",
+                formatter.hex(hit.address),
+                formatter.hex(hit.old),
+                formatter.hex(hit.new),
+                formatter.hex(hit.pc)
+            )
+        } else if self.simulator.at_breakpoint() {
+            writeln!(
+                self.output,
+                "Breakpoint reached at {}",
+                self.simulator.formatter().hex(self.simulator.pc())
+            )
+        } else {
+            writeln!(
+                self.output,
+                "Stopped at {}",
+                self.simulator.formatter().hex(self.simulator.pc())
+            )
+        }
+    }
+
+    fn registers(&mut self) -> Result<(), Error> {
+        let registers = self.simulator.registers();
+        let formatter = self.simulator.formatter();
+
+        writeln!(
+            self.output,
+            "PC: {} IR: {} CC: {}",
+            formatter.hex(self.simulator.pc()),
+            formatter.hex(self.simulator.ir()),
+            self.simulator.condition_code()
+        )?;
+        for (row, values) in registers.chunks(4).enumerate() {
+            writeln!(
+                self.output,
+                "{}",
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &value)| format!("R{}: {}", row * 4 + i, formatter.hex(value)))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a number written in LC-3 hex (`x3000`), C hex (`0x3000`), or decimal (`#12` or `12`)
+fn parse_number(number: &str) -> Option<u16> {
+    if let Some(hex) = number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix('x'))
+        .or_else(|| number.strip_prefix('X'))
+    {
+        u16::from_str_radix(hex, 16).ok()
+    } else {
+        number.trim_start_matches('#').parse().ok()
+    }
+}
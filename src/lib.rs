@@ -1 +1,4 @@
+pub mod debugger;
+pub mod scheduler;
 pub mod simulator;
+pub mod split_view;
@@ -0,0 +1,128 @@
+use crate::simulator::Simulator;
+
+/// How one of the machines run by a [`CoScheduler`](struct.CoScheduler.html) is doing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MachineStatus {
+    pub running: bool,
+    pub instructions: u64,
+}
+
+/// Runs several simulators in a deterministic round robin, giving each running machine up to
+/// `quantum` instructions per turn, to show how cooperative multitasking behaves.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::scheduler::{CoScheduler, MachineStatus};
+/// use lc3simlib::simulator::Simulator;
+///
+/// let mut scheduler = CoScheduler::new(4)
+///     .with_machine(
+///         Simulator::from_source(
+///             0x3000,
+///             "        ADD R1, R1, #1
+///                      STI R2, MCR
+///              MCR     .FILL xFFFE",
+///         )
+///         .unwrap(),
+///     )
+///     .with_machine(
+///         Simulator::from_source(
+///             0x3000,
+///             "        ADD R1, R1, #5
+///              LOOP    ADD R1, R1, #-1
+///                      BRp LOOP
+///                      STI R2, MCR
+///              MCR     .FILL xFFFE",
+///         )
+///         .unwrap(),
+///     );
+///
+/// assert!(scheduler.run_round());
+/// assert_eq!(
+///     scheduler.status(),
+///     [
+///         MachineStatus { running: false, instructions: 2 },
+///         MachineStatus { running: true, instructions: 4 },
+///     ]
+/// );
+///
+/// scheduler.run();
+/// assert_eq!(scheduler.rounds(), 3);
+/// assert_eq!(
+///     scheduler.status(),
+///     [
+///         MachineStatus { running: false, instructions: 2 },
+///         MachineStatus { running: false, instructions: 12 },
+///     ]
+/// );
+/// ```
+pub struct CoScheduler {
+    machines: Vec<Simulator>,
+    quantum: u64,
+    rounds: u64,
+}
+
+impl CoScheduler {
+    /// A scheduler giving each machine `quantum` instructions a turn (at least 1)
+    #[must_use]
+    pub fn new(quantum: u64) -> Self {
+        Self {
+            machines: Vec::new(),
+            quantum: quantum.max(1),
+            rounds: 0,
+        }
+    }
+
+    /// Add a machine, which takes its turn after every machine added before it
+    #[must_use]
+    pub fn with_machine(mut self, simulator: Simulator) -> Self {
+        self.machines.push(simulator);
+        self
+    }
+
+    /// The machines being run, in the order they take their turns
+    #[must_use]
+    pub fn machines(&self) -> &[Simulator] {
+        &self.machines
+    }
+
+    /// How many rounds have been run
+    #[must_use]
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+
+    /// Give every running machine one turn, returning whether any are still running
+    /// afterwards.
+    pub fn run_round(&mut self) -> bool {
+        let quantum = self.quantum;
+
+        self.machines.iter_mut().for_each(|machine| {
+            for _ in 0..quantum {
+                if !machine.step().running {
+                    break;
+                }
+            }
+        });
+        self.rounds += 1;
+
+        self.machines.iter().any(Simulator::is_running)
+    }
+
+    /// Run rounds until every machine has halted
+    pub fn run(&mut self) {
+        while self.machines.iter().any(Simulator::is_running) && self.run_round() {}
+    }
+
+    /// Whether each machine is still running, and how many instructions it's executed
+    #[must_use]
+    pub fn status(&self) -> Vec<MachineStatus> {
+        self.machines
+            .iter()
+            .map(|machine| MachineStatus {
+                running: machine.is_running(),
+                instructions: machine.instruction_count(),
+            })
+            .collect()
+    }
+}
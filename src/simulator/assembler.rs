@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+use super::{Instruction, Operand};
+
+/// An assembled program, ready to be loaded into memory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program {
+    /// Where the first word is loaded
+    pub origin: u16,
+    pub words: Vec<u16>,
+    /// The address of every label
+    pub symbols: HashMap<String, u16>,
+}
+
+/// A line of source, split into its label, and its opcode or directive with the operands
+struct Line<'a> {
+    number: usize,
+    label: Option<&'a str>,
+    operation: Option<String>,
+    operands: Vec<&'a str>,
+    string: Option<String>,
+}
+
+/// Assemble a small subset of LC-3 assembly, starting at `origin` unless the source has an
+/// `.ORIG` of its own.
+///
+/// Labels, the `.ORIG`, `.FILL`, `.BLKW`, `.STRINGZ`, and `.END` directives, every
+/// instruction, and the trap aliases (`GETC`, `OUT`, `PUTS`, `IN`, `PUTSP`, and `HALT`) are
+/// understood. Numbers are written as `#10`, `10`, `xA`, or `0xA`, and comments start with
+/// `;`.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::assembler::assemble;
+///
+/// let program = assemble(
+///     "        .ORIG x3000
+///      LOOP    ADD R0, R0, #1 ; Count up \"forever\"
+///              BRnzp LOOP
+///      NAME    .STRINGZ \"Hi\" ; Not \"Hi;\"
+///              .END",
+///     0x0000,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(program.origin, 0x3000);
+/// assert_eq!(program.words, [0x1021, 0x0FFE, 0x0048, 0x0069, 0x0000]);
+/// assert_eq!(program.symbols["NAME"], 0x3002);
+/// ```
+///
+/// # Errors
+/// Will return Err, naming the line, if the source can't be assembled
+pub fn assemble(source: &str, origin: u16) -> Result<Program, Error> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(number, line)| split(number + 1, line))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The first pass works out where everything goes
+    let mut origin = origin;
+    let mut address = origin;
+    let mut symbols = HashMap::new();
+    let mut statements = Vec::new();
+
+    for line in &lines {
+        if let Some(label) = line.label {
+            if symbols.insert(String::from(label), address).is_some() {
+                return Err(error(line.number, format!("{} is defined twice", label)));
+            }
+        }
+
+        let operation = match line.operation {
+            Some(ref operation) => operation.as_str(),
+            None => continue,
+        };
+
+        match operation {
+            ".ORIG" => {
+                if !statements.is_empty() || line.label.is_some() {
+                    return Err(error(
+                        line.number,
+                        "The .ORIG must come before anything else",
+                    ));
+                }
+                origin = word(line, line.operands.first().copied())? as u16;
+                address = origin;
+                continue;
+            }
+            ".END" => break,
+            _ => {}
+        }
+
+        let size = match operation {
+            ".BLKW" => match number(line.operands.first().copied().unwrap_or("")) {
+                Some(size @ 1..=0xFFFF) => size as u16,
+                _ => return Err(error(line.number, "Expected a number of words to reserve")),
+            },
+            ".STRINGZ" => match line.string {
+                Some(ref string) => string.chars().count() as u16 + 1,
+                None => return Err(error(line.number, "Expected a string in double quotes")),
+            },
+            _ => 1,
+        };
+
+        statements.push((address, line));
+        address = address.wrapping_add(size);
+    }
+
+    // The second pass encodes each statement now that every label is known
+    let mut words = Vec::new();
+    for (address, line) in statements {
+        match line.operation.as_deref() {
+            Some(".FILL") => words.push(fill(line, &symbols)?),
+            Some(".BLKW") => words.extend(std::iter::repeat_n(
+                0,
+                number(line.operands[0]).unwrap() as usize,
+            )),
+            Some(".STRINGZ") => {
+                words.extend(line.string.as_ref().unwrap().chars().map(|c| c as u16));
+                words.push(0);
+            }
+            _ => words.push(u16::from(instruction(line, address, &symbols)?)),
+        }
+    }
+
+    Ok(Program {
+        origin,
+        words,
+        symbols,
+    })
+}
+
+fn error<S: Into<String>>(line: usize, message: S) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Line {}: {}", line, message.into()),
+    )
+}
+
+fn is_operation(token: &str) -> bool {
+    let token = token.to_ascii_uppercase();
+
+    token.starts_with('.')
+        || matches!(
+            token.as_str(),
+            "ADD"
+                | "AND"
+                | "NOT"
+                | "JMP"
+                | "RET"
+                | "JSR"
+                | "JSRR"
+                | "LD"
+                | "LDI"
+                | "LDR"
+                | "LEA"
+                | "ST"
+                | "STI"
+                | "STR"
+                | "TRAP"
+                | "RTI"
+                | "NOP"
+                | "GETC"
+                | "OUT"
+                | "PUTS"
+                | "IN"
+                | "PUTSP"
+                | "HALT"
+        )
+        || branch_flags(&token).is_some()
+}
+
+/// The n, z, and p flags of a branch opcode, such as `BRzp`
+fn branch_flags(opcode: &str) -> Option<(bool, bool, bool)> {
+    let flags = opcode.strip_prefix("BR")?;
+
+    if flags.is_empty() {
+        return Some((true, true, true));
+    }
+
+    let mut rest = flags;
+    let mut flag = |c: char| match rest.strip_prefix(c) {
+        Some(remaining) => {
+            rest = remaining;
+            true
+        }
+        None => false,
+    };
+    let (n, z, p) = (flag('N'), flag('Z'), flag('P'));
+
+    if rest.is_empty() {
+        Some((n, z, p))
+    } else {
+        None
+    }
+}
+
+fn split(number: usize, line: &str) -> Result<Line<'_>, Error> {
+    // A string may hold a ';', and a comment may hold a '"', so whichever comes first wins
+    let (code, string) = match line.find(['"', ';']) {
+        Some(start) if line[start..].starts_with('"') => {
+            let (string, end) = string_literal(&line[start + 1..])
+                .ok_or_else(|| error(number, "Unterminated string"))?;
+            let rest = &line[start + 1 + end..];
+            if !rest.split(';').next().unwrap_or("").trim().is_empty() {
+                return Err(error(number, "Unexpected text after the string"));
+            }
+            (&line[..start], Some(string))
+        }
+        Some(start) => (&line[..start], None),
+        None => (line, None),
+    };
+
+    let mut tokens = code
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty());
+
+    let mut label = None;
+    let mut operation = tokens.next();
+    if let Some(token) = operation.filter(|token| !is_operation(token)) {
+        label = Some(token.trim_end_matches(':'));
+        operation = tokens.next();
+    }
+
+    if let Some(token) = operation.filter(|token| !is_operation(token)) {
+        return Err(error(number, format!("Unknown instruction {}", token)));
+    }
+
+    Ok(Line {
+        number,
+        label,
+        operation: operation.map(str::to_ascii_uppercase),
+        operands: tokens.collect(),
+        string,
+    })
+}
+
+/// The contents of a string literal (starting after its opening quote), and how many bytes
+/// it took up including the closing quote
+fn string_literal(source: &str) -> Option<(String, usize)> {
+    let mut string = String::new();
+    let mut chars = source.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((string, i + 1)),
+            '\\' => string.push(match chars.next()?.1 {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                c => c,
+            }),
+            c => string.push(c),
+        }
+    }
+
+    None
+}
+
+/// A number written in decimal (`#10` or `10`) or hex (`xA` or `0xA`)
+fn number(token: &str) -> Option<i32> {
+    let (negative, digits) = match token.trim_start_matches('#').strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token.trim_start_matches('#')),
+    };
+
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix('x'))
+        .or_else(|| digits.strip_prefix('X'))
+    {
+        Some(hex) => i32::from_str_radix(hex, 16).ok()?,
+        None if token.starts_with('#') || digits.starts_with(|c: char| c.is_ascii_digit()) => {
+            digits.parse().ok()?
+        }
+        None => return None,
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+/// A number which fits in a word, either signed or unsigned
+fn word(line: &Line, token: Option<&str>) -> Result<i32, Error> {
+    match token.and_then(number) {
+        Some(value @ -0x8000..=0xFFFF) => Ok(value),
+        Some(_) => Err(error(line.number, "The value doesn't fit in a word")),
+        None => Err(error(line.number, "Expected a number")),
+    }
+}
+
+fn fill(line: &Line, symbols: &HashMap<String, u16>) -> Result<u16, Error> {
+    match line.operands.first() {
+        Some(&label) if symbols.contains_key(label) => Ok(symbols[label]),
+        token => word(line, token.copied()).map(|value| value as u16),
+    }
+}
+
+fn register(line: &Line, index: usize) -> Result<usize, Error> {
+    let token = line.operands.get(index).copied().unwrap_or("");
+
+    match token.as_bytes() {
+        [b'R' | b'r', digit @ b'0'..=b'7'] => Ok(usize::from(digit - b'0')),
+        _ => Err(error(
+            line.number,
+            format!("Expected a register (R0 to R7), found '{}'", token),
+        )),
+    }
+}
+
+/// A signed value which must fit in `bits` bits
+fn signed(line: &Line, value: i32, bits: u32) -> Result<i16, Error> {
+    let limit = 1 << (bits - 1);
+
+    if (-limit..limit).contains(&value) {
+        Ok(value as i16)
+    } else {
+        Err(error(
+            line.number,
+            format!("{} doesn't fit in {} bits", value, bits),
+        ))
+    }
+}
+
+fn immediate(line: &Line, index: usize, bits: u32) -> Result<i16, Error> {
+    match line.operands.get(index).copied().and_then(number) {
+        Some(value) => signed(line, value, bits),
+        None => Err(error(line.number, "Expected an immediate value")),
+    }
+}
+
+/// The offset from the instruction after `address` to a label, or an offset written as a
+/// number
+fn offset(
+    line: &Line,
+    index: usize,
+    address: u16,
+    bits: u32,
+    symbols: &HashMap<String, u16>,
+) -> Result<i16, Error> {
+    let token = line.operands.get(index).copied().unwrap_or("");
+
+    match symbols.get(token) {
+        Some(&target) => signed(
+            line,
+            i32::from(target.wrapping_sub(address.wrapping_add(1)) as i16),
+            bits,
+        ),
+        None => match number(token) {
+            Some(value) => signed(line, value, bits),
+            None => Err(error(line.number, format!("Unknown label '{}'", token))),
+        },
+    }
+}
+
+fn operand(line: &Line, index: usize) -> Result<Operand, Error> {
+    match register(line, index) {
+        Ok(register) => Ok(Operand::Register(register)),
+        Err(_) => immediate(line, index, 5).map(Operand::Immediate),
+    }
+}
+
+fn instruction(
+    line: &Line,
+    address: u16,
+    symbols: &HashMap<String, u16>,
+) -> Result<Instruction, Error> {
+    let operation = line.operation.as_deref().unwrap_or("");
+    let expected = match operation {
+        "ADD" | "AND" | "LDR" | "STR" => 3,
+        "NOT" | "LD" | "LDI" | "LEA" | "ST" | "STI" => 2,
+        "JMP" | "JSR" | "JSRR" | "TRAP" => 1,
+        opcode if opcode.starts_with("BR") => 1,
+        _ => 0,
+    };
+    if line.operands.len() != expected {
+        return Err(error(
+            line.number,
+            format!(
+                "{} expects {} operands, but found {}",
+                operation,
+                expected,
+                line.operands.len()
+            ),
+        ));
+    }
+
+    Ok(match operation {
+        "ADD" => Instruction::Add(register(line, 0)?, register(line, 1)?, operand(line, 2)?),
+        "AND" => Instruction::And(register(line, 0)?, register(line, 1)?, operand(line, 2)?),
+        "NOT" => Instruction::Not(register(line, 0)?, register(line, 1)?),
+        "LD" => Instruction::Load(register(line, 0)?, offset(line, 1, address, 9, symbols)?),
+        "LDI" => {
+            Instruction::LoadIndirect(register(line, 0)?, offset(line, 1, address, 9, symbols)?)
+        }
+        "LEA" => Instruction::LoadEffectiveAddress(
+            register(line, 0)?,
+            offset(line, 1, address, 9, symbols)?,
+        ),
+        "ST" => Instruction::Store(register(line, 0)?, offset(line, 1, address, 9, symbols)?),
+        "STI" => {
+            Instruction::StoreIndirect(register(line, 0)?, offset(line, 1, address, 9, symbols)?)
+        }
+        "LDR" => Instruction::LoadRelative(
+            register(line, 0)?,
+            register(line, 1)?,
+            immediate(line, 2, 6)?,
+        ),
+        "STR" => Instruction::StoreRelative(
+            register(line, 0)?,
+            register(line, 1)?,
+            immediate(line, 2, 6)?,
+        ),
+        "JMP" => Instruction::Jump(register(line, 0)?),
+        "RET" => Instruction::Jump(7),
+        "JSR" => Instruction::JumpSubroutine(offset(line, 0, address, 11, symbols)?),
+        "JSRR" => Instruction::JumpSubroutineRegister(register(line, 0)?),
+        "RTI" => Instruction::ReturnFromInterrupt,
+        "NOP" => Instruction::Branch(false, false, false, 0),
+        "TRAP" => match number(line.operands[0]) {
+            Some(vector @ 0..=0xFF) => Instruction::Trap(vector as u8),
+            _ => return Err(error(line.number, "Expected a trap vector (x00 to xFF)")),
+        },
+        "GETC" => Instruction::Trap(0x20),
+        "OUT" => Instruction::Trap(0x21),
+        "PUTS" => Instruction::Trap(0x22),
+        "IN" => Instruction::Trap(0x23),
+        "PUTSP" => Instruction::Trap(0x24),
+        "HALT" => Instruction::Trap(0x25),
+        opcode => match branch_flags(opcode) {
+            Some((n, z, p)) => Instruction::Branch(n, z, p, offset(line, 0, address, 9, symbols)?),
+            None => return Err(error(line.number, format!("Unknown directive {}", opcode))),
+        },
+    })
+}
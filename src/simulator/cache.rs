@@ -0,0 +1,140 @@
+/// The shape and timing of a data cache, with sizes counted in words.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// How many words the cache holds altogether
+    pub size: usize,
+    /// How many consecutive words are brought in on a miss
+    pub line_size: usize,
+    /// How many lines each set holds (1 for a direct mapped cache)
+    pub associativity: usize,
+    /// How many cycles a hit takes
+    pub hit_latency: u64,
+    /// How many cycles a miss takes
+    pub miss_latency: u64,
+}
+
+impl Default for CacheConfig {
+    /// A 256 word direct mapped cache with 4 word lines, where a miss costs 10 cycles
+    fn default() -> Self {
+        Self {
+            size: 256,
+            line_size: 4,
+            associativity: 1,
+            hit_latency: 1,
+            miss_latency: 10,
+        }
+    }
+}
+
+/// How a cache has performed so far
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Every cycle spent accessing memory
+    pub cycles: u64,
+    /// The cycles spent waiting on misses, beyond what a hit would have taken
+    pub stall_cycles: u64,
+}
+
+impl CacheStats {
+    /// The fraction of accesses which hit, or 0 if there haven't been any
+    #[must_use]
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            accesses => self.hits as f64 / accesses as f64,
+        }
+    }
+}
+
+/// A set associative cache with least recently used replacement, which only keeps track of
+/// which lines it holds (the data itself always comes from memory).
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::{Cache, CacheConfig};
+///
+/// let mut cache = Cache::new(CacheConfig {
+///     size: 8,
+///     line_size: 4,
+///     associativity: 1,
+///     ..CacheConfig::default()
+/// });
+///
+/// assert!(!cache.access(0x3000));
+/// assert!(cache.access(0x3003));
+/// // x3008 maps to the same line as x3000, pushing it out
+/// assert!(!cache.access(0x3008));
+/// assert!(!cache.access(0x3000));
+/// assert_eq!(cache.stats().misses, 3);
+/// ```
+pub struct Cache {
+    config: CacheConfig,
+    sets: Vec<Vec<usize>>,
+    stats: CacheStats,
+}
+
+impl Cache {
+    /// # Panics
+    /// Will panic if the size isn't a whole number of sets of lines
+    #[must_use]
+    pub fn new(config: CacheConfig) -> Self {
+        let set_size = config.line_size * config.associativity;
+        assert!(
+            set_size != 0 && config.size >= set_size && config.size.is_multiple_of(set_size),
+            "A cache of {} words can't be made of {} way sets of {} word lines",
+            config.size,
+            config.associativity,
+            config.line_size
+        );
+
+        Self {
+            config,
+            sets: vec![Vec::with_capacity(config.associativity); config.size / set_size],
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Access the word at `address`, returning whether it was already in the cache
+    pub fn access(&mut self, address: u16) -> bool {
+        let line = usize::from(address) / self.config.line_size;
+        let sets = self.sets.len();
+        let set = &mut self.sets[line % sets];
+
+        // Each set is kept in order of use, most recent last
+        let hit = match set.iter().position(|&held| held == line) {
+            Some(way) => {
+                set.remove(way);
+                true
+            }
+            None => {
+                if set.len() == self.config.associativity {
+                    set.remove(0);
+                }
+                false
+            }
+        };
+        set.push(line);
+
+        if hit {
+            self.stats.hits += 1;
+            self.stats.cycles += self.config.hit_latency;
+        } else {
+            self.stats.misses += 1;
+            self.stats.cycles += self.config.miss_latency;
+            self.stats.stall_cycles += self
+                .config
+                .miss_latency
+                .saturating_sub(self.config.hit_latency);
+        }
+
+        hit
+    }
+
+    /// How the cache has performed so far
+    #[must_use]
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+}
@@ -0,0 +1,45 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// How serious a diagnostic is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Something suspicious, which the program kept running through
+    Warning,
+    /// Something which stopped the machine
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A warning or fault raised while a program was loaded or run, in a form an editor can place
+/// against the offending address.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// The address of the instruction (or word) the diagnostic is about
+    pub address: u16,
+    pub severity: Severity,
+    /// A short, stable name for the kind of diagnostic, such as `store-to-code`
+    pub code: &'static str,
+    /// The same text as the matching entry in [`Simulator::warnings`](struct.Simulator.html#method.warnings)
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "0x{:04X}: {}[{}]: {}",
+            self.address, self.severity, self.code, self.message
+        )
+    }
+}
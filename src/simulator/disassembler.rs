@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+use super::{read_object, Instruction};
+
+/// An address where two object files disassemble differently. An address only one of the
+/// files loads anything into has None for the other.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisasmDiff {
+    pub address: u16,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// The address, word, and disassembly of everything in an object file, without running it.
+///
+/// # Errors
+/// Will return Err if the file couldn't be read, or is too short to hold an origin
+pub fn disassemble_file(file: &str) -> Result<Vec<(u16, u16, String)>, Error> {
+    let buffer = read_object(file)?;
+
+    if buffer.len() < 2 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("{} is too short to be an object file", file),
+        ));
+    }
+
+    let origin = u16::from(buffer[0]) << 8 | u16::from(buffer[1]);
+
+    Ok(buffer[2..]
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(i, bytes)| {
+            let word = u16::from(bytes[0]) << 8 | u16::from(bytes[1]);
+            (
+                origin.wrapping_add(i as u16),
+                word,
+                Instruction::from(word).to_string(),
+            )
+        })
+        .collect())
+}
+
+/// Every address where the disassembly of `file_a` differs from that of `file_b`, such as
+/// between an optimised and an unoptimised build of the same program.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::disassembler::{disassemble_diff, DisasmDiff};
+///
+/// let before = std::env::temp_dir().join(format!("lc3sim_before_{}.obj", std::process::id()));
+/// let after = std::env::temp_dir().join(format!("lc3sim_after_{}.obj", std::process::id()));
+/// std::fs::write(&before, [0x30, 0x00, 0x12, 0x61, 0x12, 0x61, 0xF0, 0x25]).unwrap();
+/// std::fs::write(&after, [0x30, 0x00, 0x12, 0x61, 0x12, 0x62, 0xF0, 0x25]).unwrap();
+///
+/// assert_eq!(
+///     disassemble_diff(before.to_str().unwrap(), after.to_str().unwrap()).unwrap(),
+///     [DisasmDiff {
+///         address: 0x3001,
+///         before: Some(String::from("ADD R1, R1, #1")),
+///         after: Some(String::from("ADD R1, R1, #2")),
+///     }]
+/// );
+/// ```
+///
+/// # Errors
+/// Will return Err if either file couldn't be disassembled
+pub fn disassemble_diff(file_a: &str, file_b: &str) -> Result<Vec<DisasmDiff>, Error> {
+    let mut lines = BTreeMap::new();
+
+    for (address, _, line) in disassemble_file(file_a)? {
+        lines.entry(address).or_insert((None, None)).0 = Some(line);
+    }
+    for (address, _, line) in disassemble_file(file_b)? {
+        lines.entry(address).or_insert((None, None)).1 = Some(line);
+    }
+
+    Ok(lines
+        .into_iter()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(address, (before, after))| DisasmDiff {
+            address,
+            before,
+            after,
+        })
+        .collect())
+}
@@ -0,0 +1,71 @@
+/// How numbers are written in hex by the tracer, the disassembler, and the state report.
+///
+/// By default everything is uppercase, and each place keeps its usual prefix (`0x` in
+/// reports and the full trace, and `x` in assembly and the pretty trace). Setting a prefix
+/// uses it everywhere instead.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::Formatter;
+///
+/// let formatter = Formatter::default();
+/// assert_eq!(formatter.hex(0xABCD), "0xABCD");
+/// assert_eq!(formatter.assembly(0x25, 2), "x25");
+///
+/// let formatter = Formatter::default().with_prefix("0x").with_lowercase(true);
+/// assert_eq!(formatter.hex(0xABCD), "0xabcd");
+/// assert_eq!(formatter.assembly(0x25, 2), "0x25");
+/// assert_eq!(formatter.digits(0xABCD, 4), "abcd");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Formatter {
+    prefix: Option<String>,
+    lowercase: bool,
+}
+
+impl Formatter {
+    /// Use `prefix` in front of every hex number
+    #[must_use]
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(String::from(prefix));
+        self
+    }
+
+    /// Write hex digits in lowercase
+    #[must_use]
+    pub fn with_lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+
+    /// Just the hex digits of `value`, zero padded to `width`
+    #[must_use]
+    pub fn digits(&self, value: u16, width: usize) -> String {
+        if self.lowercase {
+            format!("{:0width$x}", value, width = width)
+        } else {
+            format!("{:0width$X}", value, width = width)
+        }
+    }
+
+    /// A full word, prefixed with `0x` unless another prefix was chosen
+    #[must_use]
+    pub fn hex(&self, value: u16) -> String {
+        format!(
+            "{}{}",
+            self.prefix.as_deref().unwrap_or("0x"),
+            self.digits(value, 4)
+        )
+    }
+
+    /// A number as written in LC-3 assembly, prefixed with `x` unless another prefix was
+    /// chosen
+    #[must_use]
+    pub fn assembly(&self, value: u16, width: usize) -> String {
+        format!(
+            "{}{}",
+            self.prefix.as_deref().unwrap_or("x"),
+            self.digits(value, width)
+        )
+    }
+}
@@ -0,0 +1,325 @@
+use std::convert::From;
+use std::fmt;
+
+use super::{sign_extend, Formatter};
+
+/// The second operand of an ADD or AND
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Register(usize),
+    Immediate(i16),
+}
+
+/// A decoded LC-3 instruction.
+///
+/// Registers are stored as their index, and offsets are stored already sign extended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// BR[n][z][p] with the n, z, and p flags, and a 9 bit PC offset
+    Branch(bool, bool, bool, i16),
+    /// ADD DR, SR1, SR2/imm5
+    Add(usize, usize, Operand),
+    /// LD DR, PCoffset9
+    Load(usize, i16),
+    /// ST SR, PCoffset9
+    Store(usize, i16),
+    /// JSR PCoffset11
+    JumpSubroutine(i16),
+    /// JSRR BaseR
+    JumpSubroutineRegister(usize),
+    /// AND DR, SR1, SR2/imm5
+    And(usize, usize, Operand),
+    /// LDR DR, BaseR, offset6
+    LoadRelative(usize, usize, i16),
+    /// STR SR, BaseR, offset6
+    StoreRelative(usize, usize, i16),
+    /// RTI
+    ReturnFromInterrupt,
+    /// NOT DR, SR
+    Not(usize, usize),
+    /// LDI DR, PCoffset9
+    LoadIndirect(usize, i16),
+    /// STI SR, PCoffset9
+    StoreIndirect(usize, i16),
+    /// JMP BaseR (RET when BaseR is R7)
+    Jump(usize),
+    /// The reserved opcode, with the remaining 12 bits of the word
+    Reserved(u16),
+    /// LEA DR, PCoffset9
+    LoadEffectiveAddress(usize, i16),
+    /// TRAP trapvect8
+    Trap(u8),
+}
+
+impl From<u16> for Instruction {
+    fn from(word: u16) -> Self {
+        let destination_register = usize::from(word >> 9 & 0b111);
+        let source_register_one = usize::from(word >> 6 & 0b111);
+        let operand = if word & 0x20 == 0 {
+            Operand::Register(usize::from(word & 0b111))
+        } else {
+            Operand::Immediate(sign_extend(word, 5))
+        };
+
+        match word >> 12 {
+            0x0 => Self::Branch(
+                word & 0x0800 != 0,
+                word & 0x0400 != 0,
+                word & 0x0200 != 0,
+                sign_extend(word, 9),
+            ),
+            0x1 => Self::Add(destination_register, source_register_one, operand),
+            0x2 => Self::Load(destination_register, sign_extend(word, 9)),
+            0x3 => Self::Store(destination_register, sign_extend(word, 9)),
+            0x4 => {
+                if word & 0x0800 == 0 {
+                    Self::JumpSubroutineRegister(source_register_one)
+                } else {
+                    Self::JumpSubroutine(sign_extend(word, 11))
+                }
+            }
+            0x5 => Self::And(destination_register, source_register_one, operand),
+            0x6 => Self::LoadRelative(
+                destination_register,
+                source_register_one,
+                sign_extend(word, 6),
+            ),
+            0x7 => Self::StoreRelative(
+                destination_register,
+                source_register_one,
+                sign_extend(word, 6),
+            ),
+            0x8 => Self::ReturnFromInterrupt,
+            0x9 => Self::Not(destination_register, source_register_one),
+            0xA => Self::LoadIndirect(destination_register, sign_extend(word, 9)),
+            0xB => Self::StoreIndirect(destination_register, sign_extend(word, 9)),
+            0xC => Self::Jump(source_register_one),
+            0xD => Self::Reserved(word & 0x0FFF),
+            0xE => Self::LoadEffectiveAddress(destination_register, sign_extend(word, 9)),
+            _ => Self::Trap(word as u8),
+        }
+    }
+}
+
+/// Encode an instruction back into its machine word.
+///
+/// Decoding is lossy only for bits the LC-3 ignores, and those are always encoded as their
+/// canonical value:
+///  - ADD and AND in register mode ignore bits 4 and 3 (encoded as 0)
+///  - JSRR ignores bits 10, 9, and 5 through 0 (encoded as 0)
+///  - RTI ignores bits 11 through 0 (encoded as 0)
+///  - NOT ignores bits 5 through 0 (encoded as 1)
+///  - JMP ignores bits 11 through 9, and 5 through 0 (encoded as 0)
+///  - TRAP ignores bits 11 through 8 (encoded as 0)
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::Instruction;
+///
+/// for word in 0..=0xFFFF_u16 {
+///     let canonical = match word >> 12 {
+///         0x1 | 0x5 if word & 0x20 == 0 => word & 0xFFE7,
+///         0x4 if word & 0x0800 == 0 => word & 0xF1C0,
+///         0x8 => 0x8000,
+///         0x9 => word | 0x003F,
+///         0xC => word & 0xF1C0,
+///         0xF => word & 0xF0FF,
+///         _ => word,
+///     };
+///
+///     assert_eq!(u16::from(Instruction::from(word)), canonical, "0x{:04X}", word);
+/// }
+///
+/// // Negative offsets only fill their own field, leaving the registers alone
+/// assert_eq!(u16::from(Instruction::LoadRelative(0, 1, -1)), 0x607F);
+/// assert_eq!(u16::from(Instruction::StoreRelative(7, 6, -32)), 0x7FA0);
+/// assert_eq!(u16::from(Instruction::LoadEffectiveAddress(1, -256)), 0xE300);
+/// ```
+impl From<Instruction> for u16 {
+    fn from(instruction: Instruction) -> Self {
+        let register = |register: usize, shift: u16| (register as u16 & 0b111) << shift;
+        let offset = |offset: i16, bits: u16| offset as u16 & ((1 << bits) - 1);
+        let operand = |operand: Operand| match operand {
+            Operand::Register(register) => register as u16 & 0b111,
+            Operand::Immediate(value) => 0x20 | offset(value, 5),
+        };
+
+        match instruction {
+            Instruction::Branch(n, z, p, pc_offset) => {
+                u16::from(n) << 11 | u16::from(z) << 10 | u16::from(p) << 9 | offset(pc_offset, 9)
+            }
+            Instruction::Add(dr, sr1, source) => {
+                0x1000 | register(dr, 9) | register(sr1, 6) | operand(source)
+            }
+            Instruction::Load(dr, pc_offset) => 0x2000 | register(dr, 9) | offset(pc_offset, 9),
+            Instruction::Store(sr, pc_offset) => 0x3000 | register(sr, 9) | offset(pc_offset, 9),
+            Instruction::JumpSubroutine(pc_offset) => 0x4800 | offset(pc_offset, 11),
+            Instruction::JumpSubroutineRegister(base) => 0x4000 | register(base, 6),
+            Instruction::And(dr, sr1, source) => {
+                0x5000 | register(dr, 9) | register(sr1, 6) | operand(source)
+            }
+            Instruction::LoadRelative(dr, base, base_offset) => {
+                0x6000 | register(dr, 9) | register(base, 6) | offset(base_offset, 6)
+            }
+            Instruction::StoreRelative(sr, base, base_offset) => {
+                0x7000 | register(sr, 9) | register(base, 6) | offset(base_offset, 6)
+            }
+            Instruction::ReturnFromInterrupt => 0x8000,
+            Instruction::Not(dr, sr) => 0x9000 | register(dr, 9) | register(sr, 6) | 0x003F,
+            Instruction::LoadIndirect(dr, pc_offset) => {
+                0xA000 | register(dr, 9) | offset(pc_offset, 9)
+            }
+            Instruction::StoreIndirect(sr, pc_offset) => {
+                0xB000 | register(sr, 9) | offset(pc_offset, 9)
+            }
+            Instruction::Jump(base) => 0xC000 | register(base, 6),
+            Instruction::Reserved(bits) => 0xD000 | bits & 0x0FFF,
+            Instruction::LoadEffectiveAddress(dr, pc_offset) => {
+                0xE000 | register(dr, 9) | offset(pc_offset, 9)
+            }
+            Instruction::Trap(vector) => 0xF000 | u16::from(vector),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(register) => write!(f, "R{}", register),
+            Operand::Immediate(value) => write!(f, "#{}", value),
+        }
+    }
+}
+
+impl Instruction {
+    /// The register the instruction writes to, if any
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Instruction;
+    ///
+    /// assert_eq!(Instruction::from(0x1A61).destination(), Some(5));
+    /// assert_eq!(Instruction::from(0x4802).destination(), Some(7));
+    /// assert_eq!(Instruction::from(0x3A02).destination(), None);
+    /// ```
+    #[must_use]
+    pub fn destination(&self) -> Option<usize> {
+        match *self {
+            Instruction::Add(dr, _, _)
+            | Instruction::And(dr, _, _)
+            | Instruction::Not(dr, _)
+            | Instruction::Load(dr, _)
+            | Instruction::LoadRelative(dr, _, _)
+            | Instruction::LoadIndirect(dr, _)
+            | Instruction::LoadEffectiveAddress(dr, _) => Some(dr),
+            Instruction::JumpSubroutine(_)
+            | Instruction::JumpSubroutineRegister(_)
+            | Instruction::Trap(_) => Some(7),
+            _ => None,
+        }
+    }
+
+    /// The address a PC relative instruction fetched from `address` refers to, or None for
+    /// any other instruction
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Instruction;
+    ///
+    /// assert_eq!(Instruction::from(0x4802).target(0x3000), Some(0x3003));
+    /// assert_eq!(Instruction::from(0x0FFE).target(0x3005), Some(0x3004));
+    /// assert_eq!(Instruction::from(0x1261).target(0x3000), None);
+    /// ```
+    #[must_use]
+    pub fn target(&self, address: u16) -> Option<u16> {
+        match *self {
+            Instruction::Branch(_, _, _, offset)
+            | Instruction::Load(_, offset)
+            | Instruction::Store(_, offset)
+            | Instruction::JumpSubroutine(offset)
+            | Instruction::LoadIndirect(_, offset)
+            | Instruction::StoreIndirect(_, offset)
+            | Instruction::LoadEffectiveAddress(_, offset) => {
+                Some(address.wrapping_add(1).wrapping_add(offset as u16))
+            }
+            _ => None,
+        }
+    }
+
+    /// The instruction as it would be written in LC-3 assembly, with any hex numbers written
+    /// by `formatter`.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Formatter, Instruction};
+    ///
+    /// let formatter = Formatter::default().with_prefix("0x").with_lowercase(true);
+    /// assert_eq!(Instruction::from(0xF025).to_assembly(&formatter), "TRAP 0x25");
+    /// assert_eq!(Instruction::from(0xDEAD).to_assembly(&formatter), ".FILL 0xdead");
+    /// ```
+    #[must_use]
+    pub fn to_assembly(&self, formatter: &Formatter) -> String {
+        match *self {
+            Instruction::Branch(false, false, false, _) => String::from("NOP"),
+            Instruction::Branch(n, z, p, offset) => format!(
+                "BR{}{}{} #{}",
+                if n { "n" } else { "" },
+                if z { "z" } else { "" },
+                if p { "p" } else { "" },
+                offset
+            ),
+            Instruction::Add(dr, sr1, operand) => format!("ADD R{}, R{}, {}", dr, sr1, operand),
+            Instruction::Load(dr, offset) => format!("LD R{}, #{}", dr, offset),
+            Instruction::Store(sr, offset) => format!("ST R{}, #{}", sr, offset),
+            Instruction::JumpSubroutine(offset) => format!("JSR #{}", offset),
+            Instruction::JumpSubroutineRegister(base) => format!("JSRR R{}", base),
+            Instruction::And(dr, sr1, operand) => format!("AND R{}, R{}, {}", dr, sr1, operand),
+            Instruction::LoadRelative(dr, base, offset) => {
+                format!("LDR R{}, R{}, #{}", dr, base, offset)
+            }
+            Instruction::StoreRelative(sr, base, offset) => {
+                format!("STR R{}, R{}, #{}", sr, base, offset)
+            }
+            Instruction::ReturnFromInterrupt => String::from("RTI"),
+            Instruction::Not(dr, sr) => format!("NOT R{}, R{}", dr, sr),
+            Instruction::LoadIndirect(dr, offset) => format!("LDI R{}, #{}", dr, offset),
+            Instruction::StoreIndirect(sr, offset) => format!("STI R{}, #{}", sr, offset),
+            Instruction::Jump(7) => String::from("RET"),
+            Instruction::Jump(base) => format!("JMP R{}", base),
+            Instruction::Reserved(bits) => {
+                format!(".FILL {}", formatter.assembly(0xD000 | bits, 4))
+            }
+            Instruction::LoadEffectiveAddress(dr, offset) => format!("LEA R{}, #{}", dr, offset),
+            Instruction::Trap(vector) => {
+                format!("TRAP {}", formatter.assembly(u16::from(vector), 2))
+            }
+        }
+    }
+}
+
+/// Instructions are displayed as they would be written in LC-3 assembly, with offsets
+/// shown as signed decimal immediates.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::{Instruction, Operand};
+///
+/// assert_eq!(Instruction::from(0x1261).to_string(), "ADD R1, R1, #1");
+/// assert_eq!(Instruction::from(0xA5FC).to_string(), "LDI R2, #-4");
+/// assert_eq!(Instruction::from(0x0E05).to_string(), "BRnzp #5");
+/// assert_eq!(Instruction::from(0xC1C0).to_string(), "RET");
+/// assert_eq!(Instruction::from(0xF025).to_string(), "TRAP x25");
+///
+/// // Instructions built by hand display the same way as decoded ones
+/// assert_eq!(
+///     Instruction::Add(0, 1, Operand::Immediate(3)).to_string(),
+///     "ADD R0, R1, #3"
+/// );
+/// assert_eq!(Instruction::LoadIndirect(2, -4).to_string(), "LDI R2, #-4");
+/// assert_eq!(Instruction::Branch(true, true, true, -1).to_string(), "BRnzp #-1");
+/// ```
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_assembly(&Formatter::default()))
+    }
+}
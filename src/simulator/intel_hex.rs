@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+
+/// The highest byte address there is memory for, as each of the 0x10000 words is two bytes
+const LAST_BYTE: u32 = 0x1_FFFF;
+
+/// Parse the records of an Intel HEX file, giving every byte it sets by its byte address.
+///
+/// Data, end of file, and extended segment and linear address records are understood. Start
+/// address records are accepted but ignored, since a program starts at its first word.
+pub(crate) fn parse(text: &str, file: &str) -> Result<BTreeMap<u32, u8>, Error> {
+    let mut bytes = BTreeMap::new();
+    let mut base = 0;
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let invalid = |message: String| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Line {} of {} {}", number + 1, file, message),
+            )
+        };
+
+        let digits = line
+            .strip_prefix(':')
+            .ok_or_else(|| invalid(String::from("doesn't start with ':'")))?;
+
+        if digits.len() % 2 != 0 || !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(invalid(String::from("isn't made of pairs of hex digits")));
+        }
+
+        let record = (0..digits.len())
+            .step_by(2)
+            .map(|at| u8::from_str_radix(&digits[at..at + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+
+        if record.len() < 5 {
+            return Err(invalid(String::from("is too short to be a record")));
+        }
+
+        let length = usize::from(record[0]);
+        if record.len() != length + 5 {
+            return Err(invalid(format!(
+                "claims {} bytes of data, but has {}",
+                length,
+                record.len() - 5
+            )));
+        }
+
+        let checksum = record[record.len() - 1];
+        let expected = record[..record.len() - 1]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+            .wrapping_neg();
+        if checksum != expected {
+            return Err(invalid(format!(
+                "has checksum 0x{:02X}, but its contents need 0x{:02X}",
+                checksum, expected
+            )));
+        }
+
+        let offset = u32::from(record[1]) << 8 | u32::from(record[2]);
+        let data = &record[4..record.len() - 1];
+        let value = || {
+            data.iter()
+                .fold(0, |value, &byte| value << 8 | u32::from(byte))
+        };
+
+        match record[3] {
+            0x00 => {
+                for (i, &byte) in data.iter().enumerate() {
+                    // Worked out in 64 bits, as a large enough base can overflow 32
+                    let address = u64::from(base) + u64::from(offset) + i as u64;
+                    if address > u64::from(LAST_BYTE) {
+                        return Err(invalid(format!(
+                            "sets byte 0x{:05X}, which is past the end of memory",
+                            address
+                        )));
+                    }
+                    bytes.insert(address as u32, byte);
+                }
+            }
+            0x01 => break,
+            0x02 if length == 2 => base = value() << 4,
+            0x04 if length == 2 => base = value() << 16,
+            0x03 | 0x05 if length == 4 => {}
+            0x02..=0x05 => {
+                return Err(invalid(format!(
+                    "has {} bytes of data, which is wrong for a record of type {:02X}",
+                    length, record[3]
+                )))
+            }
+            kind => {
+                return Err(invalid(format!(
+                    "has record type {:02X}, which isn't understood",
+                    kind
+                )))
+            }
+        }
+    }
+
+    Ok(bytes)
+}
@@ -0,0 +1,54 @@
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind};
+
+use serde::Deserialize;
+
+/// What a region of memory holds
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionKind {
+    Code,
+    Data,
+    Stack,
+}
+
+/// An inclusive range of addresses, and what they hold
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub struct Region {
+    pub start: u16,
+    pub end: u16,
+    pub kind: RegionKind,
+}
+
+/// Annotations describing a program's memory, loaded from a JSON file alongside its object
+/// file, such as:
+///
+/// ```json
+/// { "regions": [{ "start": 12288, "end": 12303, "kind": "code" }] }
+/// ```
+///
+/// Addresses are plain JSON numbers, and where regions overlap the first one listed wins.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct Metadata {
+    pub regions: Vec<Region>,
+}
+
+impl Metadata {
+    /// Read the metadata from a JSON file.
+    ///
+    /// # Errors
+    /// Will return Err if the file couldn't be read, or isn't valid metadata
+    pub fn from_file(file: &str) -> Result<Self, Error> {
+        serde_json::from_reader(BufReader::new(File::open(file)?))
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// What the word at `address` holds, if it's been annotated
+    #[must_use]
+    pub fn kind_of(&self, address: u16) -> Option<RegionKind> {
+        self.regions
+            .iter()
+            .find(|region| region.start <= address && address <= region.end)
+            .map(|region| region.kind)
+    }
+}
@@ -1,20 +1,89 @@
-use std::fs::File;
-use std::io::{Error, ErrorKind, Read, Write};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{Cursor, Error, ErrorKind, Read, Write};
+use std::rc::Rc;
 
+use log::{log, Level, LevelFilter};
+use serde::{Deserialize, Serialize};
+
+pub mod assembler;
+pub mod cache;
+pub mod diagnostics;
+pub mod disassembler;
+pub mod formatter;
+pub mod instruction;
+mod intel_hex;
+pub mod metadata;
+pub mod prediction;
 pub mod reader;
 pub mod tracer;
 pub mod writer;
 
+pub use cache::{Cache, CacheConfig, CacheStats};
+pub use diagnostics::{Diagnostic, Severity};
+pub use formatter::Formatter;
+pub use instruction::{Instruction, Operand};
+pub use metadata::{Metadata, Region, RegionKind};
+pub use prediction::{Branch, PredictionStats, Predictor, PredictorKind};
 pub use reader::Reader;
-pub use tracer::{Trace, Tracer};
+pub use tracer::{Trace, TraceFormat, TraceRecord, Tracer};
 pub use writer::Writer;
 
+/// The start of the memory mapped device registers
+const DEVICE_REGISTERS: usize = 0xFE00;
+
 const CLK: usize = 0xFFFE;
 const KBSR: usize = 0xFE00;
 const KBDR: usize = 0xFE02;
 const DSR: usize = 0xFE04;
 const DDR: usize = 0xFE06;
 
+/// Where user programs start, with everything below belonging to the operating system
+const USER_SPACE: u16 = 0x3000;
+
+/// How far past a label an address can be for the trace to show it relative to that label
+const NEARBY_LABEL: u16 = 0x20;
+
+/// How many entries there are in the trap vector table
+const TRAP_TABLE_LENGTH: u16 = 0x0100;
+
+/// The start of the interrupt vector table, which holds the handlers for exceptions and
+/// interrupts
+const INTERRUPT_TABLE: u16 = 0x0100;
+
+/// The exception raised when user code executes an instruction only the supervisor may
+const PRIVILEGE_VIOLATION: u16 = 0x00;
+
+/// The exception raised when an instruction with the reserved opcode is executed
+const ILLEGAL_OPCODE: u16 = 0x01;
+
+/// The bit of the PSR which is set while the processor is in user mode
+const PSR_USER: u16 = 0x8000;
+
+/// Where the supervisor stack starts, until user code has been entered and left
+const INITIAL_SSP: u16 = 0x3000;
+
+/// Where the user stack starts, until supervisor code has been entered and left
+const INITIAL_USP: u16 = 0xFE00;
+
+/// The standard traps, which the built-in trap routines handle
+const TRAP_GETC: u16 = 0x20;
+const TRAP_OUT: u16 = 0x21;
+const TRAP_PUTS: u16 = 0x22;
+const TRAP_IN: u16 = 0x23;
+const TRAP_PUTSP: u16 = 0x24;
+const TRAP_HALT: u16 = 0x25;
+
+/// The trap which prints R0 as a signed decimal number, when the decimal trap is enabled
+const TRAP_PUTDEC: u16 = 0x31;
+
+/// How often (in instructions) the input is checked for ESC by default
+const INTERRUPT_CHECK_INTERVAL: u64 = 1000;
+
+/// How many times in a row writing to the display may fail by default before the machine
+/// gives up
+const DISPLAY_RETRIES: usize = 16;
+
 const OPCODE_BR: u16 = 0x0000;
 const OPCODE_ADD: u16 = 0x1000;
 const OPCODE_LD: u16 = 0x2000;
@@ -32,25 +101,537 @@ const RESERVED: u16 = 0xD000;
 const OPCODE_LEA: u16 = 0xE000;
 const OPCODE_TRAP: u16 = 0xF000;
 
+/// What to do when a program being loaded doesn't fit between its origin and the end of memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Refuse to load the program
+    Error,
+    /// Continue loading the rest of the program from address 0x0000
+    Wrap,
+}
+
+/// What happened in a single [`step`](struct.Simulator.html#method.step) of the machine
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepResult {
+    /// Whether the machine is still running afterwards
+    pub running: bool,
+    /// The instruction which was executed, or None if the machine had already stopped
+    pub instruction: Option<Instruction>,
+    /// Which way the instruction went, if it was a branch
+    pub branch: Option<Branch>,
+}
+
+/// The state of the machine at some point, as taken by
+/// [`snapshot`](struct.Simulator.html#method.snapshot). The program's input, output, and trace
+/// aren't part of it, since they can't be wound back.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Every word of memory, including the device registers
+    pub memory: Vec<u16>,
+    pub registers: [u16; 8],
+    pub pc: u16,
+    pub ir: u16,
+    /// The processor status register, which holds the condition codes
+    pub psr: u16,
+    /// The stack pointer which isn't in use, for whichever mode the processor isn't in
+    pub saved_ssp: u16,
+    pub saved_usp: u16,
+}
+
+/// One of the cycles an instruction is executed over, as seen by
+/// [`step_cycle`](struct.Simulator.html#method.step_cycle)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// The instruction is read into the IR, and the PC incremented
+    Fetch,
+    /// The opcode is looked at to decide what happens next
+    Decode,
+    /// The source registers (or the PC) are read
+    OperandRead,
+    /// The result, address, or branch target is worked out
+    Alu,
+    /// Memory is read from or written to, including the trap vector table and the stack
+    Memory,
+    /// The result is written to the destination register (or the PC)
+    Writeback,
+}
+
+impl Phase {
+    /// Every phase an instruction goes through, in order. TRAP reads its routine's address
+    /// from the trap vector table, and RTI pops the PC and PSR off the stack, so both access
+    /// memory as loads do.
+    fn of(instruction: u16) -> &'static [Phase] {
+        match instruction & 0xF000 {
+            OPCODE_LD | OPCODE_LDR | OPCODE_LDI | OPCODE_TRAP | OPCODE_RTI => &[
+                Phase::Fetch,
+                Phase::Decode,
+                Phase::OperandRead,
+                Phase::Alu,
+                Phase::Memory,
+                Phase::Writeback,
+            ],
+            OPCODE_ST | OPCODE_STR | OPCODE_STI => &[
+                Phase::Fetch,
+                Phase::Decode,
+                Phase::OperandRead,
+                Phase::Alu,
+                Phase::Memory,
+            ],
+            _ => &[
+                Phase::Fetch,
+                Phase::Decode,
+                Phase::OperandRead,
+                Phase::Alu,
+                Phase::Writeback,
+            ],
+        }
+    }
+}
+
+/// What a program sees when it asks for more input than was provided.
+///
+/// Halting is reported as a warning through the `log` facade.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum EofPolicy {
+    /// Stop the machine, letting the user know the program needed more input
+    #[default]
+    Halt,
+    /// Keep handing the program this byte, such as EOT (0x04)
+    Sentinel(u8),
+    /// Never report the keyboard as ready again, leaving the program waiting for someone to
+    /// type. Nobody can type into a file or buffer, so input from one stops the machine as
+    /// `Halt` does.
+    Block,
+}
+
+/// Why the machine stopped running
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The clock was stopped by the program, as the HALT routine does
+    Halted,
+    /// The program asked for more input than was provided
+    OutOfInput,
+    /// ESC was pressed
+    UserInterrupt,
+    /// User code sent the PC into the trap vector table, which holds addresses rather than
+    /// code
+    NullExecution,
+    /// Something went wrong, such as one of the optional checks failing
+    Fault(String),
+    /// Execution paused at the breakpoint at this address, and can be continued
+    Breakpoint(u16),
+    /// Execution paused after an access to watched memory, and can be continued
+    Watchpoint(WatchHit),
+}
+
+/// The end state of a run of [`simulate_pure`](fn.simulate_pure.html)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PureOutcome {
+    pub registers: [u16; 8],
+    /// The address, original value, and final value of each word the program changed
+    pub memory_delta: Vec<(u16, u16, u16)>,
+    /// Every byte written to the display
+    pub output: Vec<u8>,
+    pub instructions: u64,
+    /// Why the machine stopped, or None if it was still running once the limit was reached
+    pub halt_reason: Option<HaltReason>,
+}
+
+/// Run `image` from `origin`, with nothing but `input` to read, for at most `limit`
+/// instructions. Everything happens in memory, without an operating system, and nothing a
+/// program does can make this panic, which makes it suitable for property testing.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::{simulate_pure, HaltReason};
+/// use proptest::prelude::*;
+///
+/// // ADD R1, R1, #1, STI R2 through the word after it, xFFFE (the MCR)
+/// let outcome = simulate_pure(&[0x1261, 0xB400, 0xFFFE], 0x3000, &[], 100);
+/// assert_eq!(outcome.registers[1], 1);
+/// assert_eq!(outcome.instructions, 2);
+/// assert_eq!(outcome.halt_reason, Some(HaltReason::Halted));
+///
+/// proptest!(|(image in prop::collection::vec(any::<u16>(), 0..64),
+///             input in prop::collection::vec(any::<u8>(), 0..4))| {
+///     let outcome = simulate_pure(&image, 0x3000, &input, 1000);
+///     prop_assert!(outcome.instructions <= 1000);
+///     prop_assert!(outcome.halt_reason.is_some() || outcome.instructions == 1000);
+/// });
+/// ```
+#[must_use]
+pub fn simulate_pure(image: &[u16], origin: u16, input: &[u8], limit: u64) -> PureOutcome {
+    let mut simulator = Simulator::new(
+        Reader::Buffer(Cursor::new(input.to_vec())),
+        Writer::Buffer(Vec::new()),
+        Tracer::NoTrace,
+    )
+    .with_overflow_policy(OverflowPolicy::Wrap)
+    .with_binary_output(true);
+
+    // Loading can only fail when a program that doesn't fit is refused, and it's wrapped instead
+    let _ = simulator.load_words(origin, image);
+
+    while simulator.instruction_count() < limit && simulator.step().running {}
+
+    PureOutcome {
+        registers: simulator.registers(),
+        memory_delta: simulator.memory_delta(),
+        output: match simulator.display {
+            Writer::Buffer(ref output) => output.clone(),
+            _ => Vec::new(),
+        },
+        instructions: simulator.instruction_count(),
+        halt_reason: simulator.halt_reason(),
+    }
+}
+
+/// The state the machine should be in after an instruction, for
+/// [`assert_matches`](struct.Simulator.html#method.assert_matches)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpectedState {
+    pub pc: u16,
+    pub registers: [u16; 8],
+    /// One of 'N', 'Z', or 'P'
+    pub condition_code: char,
+}
+
+/// What makes a breakpoint stop execution
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakpointKind {
+    /// Reaching the instruction at this address
+    Address(u16),
+    /// User code writing to this register
+    RegisterWrite(usize),
+    /// A store to anywhere between these addresses (inclusive)
+    Region(u16, u16),
+    /// An access to this address
+    Watch(u16, WatchKind),
+}
+
+/// Which accesses to memory a watchpoint pauses execution on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn covers(self, access: WatchKind) -> bool {
+        self == WatchKind::ReadWrite || self == access
+    }
+}
+
+/// A breakpoint, along with the id it was given when it was added
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BreakpointInfo {
+    pub id: usize,
+    pub kind: BreakpointKind,
+}
+
+/// An access to watched memory. A read leaves the value unchanged, so `old` and `new` are both
+/// the value read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub address: u16,
+    /// Whether the access was a read or a write
+    pub kind: WatchKind,
+    pub old: u16,
+    pub new: u16,
+    /// The address of the instruction which did the store
+    pub pc: u16,
+}
+
+/// One of the condition codes, which the PSR holds exactly one of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConditionCode {
+    Negative,
+    Zero,
+    Positive,
+}
+
+impl ConditionCode {
+    /// The bit of the PSR which holds this condition code
+    const fn bits(self) -> u16 {
+        match self {
+            ConditionCode::Negative => 0b100,
+            ConditionCode::Zero => 0b010,
+            ConditionCode::Positive => 0b001,
+        }
+    }
+}
+
+/// What a program sees when it reads the DDR, which real hardware doesn't define
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DdrRead {
+    /// Always this value
+    Fixed(u16),
+    /// The last value written to it
+    LastWritten,
+}
+
+impl Default for DdrRead {
+    fn default() -> Self {
+        Self::Fixed(0x0000)
+    }
+}
+
+/// How many cycles the processor stalls for each time a program polls a device's status
+/// register, as it waits on the device
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeviceLatency {
+    /// The wait for each read of the KBSR
+    pub keyboard: u64,
+    /// The wait for each read of the DSR
+    pub display: u64,
+}
+
+/// Report something about the running program through the `log` facade, so that a host
+/// application can capture or filter it. Without a logger installed it goes to stderr.
+fn diagnostic(level: Level, message: &str) {
+    if logger_installed() {
+        log!(level, "{}", message);
+    } else {
+        eprintln!("\r\n--- {} ---\r", message);
+    }
+}
+
+/// Whether an application has installed a logger. The `log` facade doesn't say, but the
+/// stand-in it uses until then never takes anything, while any real logger takes errors.
+fn logger_installed() -> bool {
+    log::max_level() != LevelFilter::Off
+        || log::logger().enabled(
+            &log::Metadata::builder()
+                .level(Level::Error)
+                .target(module_path!())
+                .build(),
+        )
+}
+
 const fn sign_extend(val: u16, length: u16) -> i16 {
     (val << (16 - length)) as i16 >> (16 - length)
 }
 
+/// Open the program's input and output, using the keyboard and terminal where no file is
+/// given. Opening the output truncates it, so using the same file for both is refused
+/// rather than losing the input before it's read.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::open_files;
+/// use std::io::ErrorKind;
+///
+/// let file = std::env::temp_dir().join(format!("lc3sim_input_{}.txt", std::process::id()));
+/// std::fs::write(&file, "input").unwrap();
+/// let file = file.to_str().unwrap();
+///
+/// let error = open_files(Some(file), Some(file)).err().unwrap();
+/// assert_eq!(error.kind(), ErrorKind::InvalidInput);
+/// assert_eq!(std::fs::read_to_string(file).unwrap(), "input");
+///
+/// // A file which can't be opened is reported rather than panicking
+/// let missing = std::env::temp_dir()
+///     .join(format!("lc3sim_open_files_missing_{}.txt", std::process::id()));
+/// let error = open_files(missing.to_str(), Some(file)).err().unwrap();
+/// assert_eq!(error.kind(), ErrorKind::NotFound);
+/// assert_eq!(std::fs::read_to_string(file).unwrap(), "input");
+/// ```
+///
+/// # Errors
+/// Will return Err if the input and output are the same file, if the input couldn't be
+/// opened, or if the output couldn't be created
+pub fn open_files(input: Option<&str>, output: Option<&str>) -> Result<(Reader, Writer), Error> {
+    if let (Some(input), Some(output)) = (input, output) {
+        let same = input == output
+            || matches!(
+                (fs::canonicalize(input), fs::canonicalize(output)),
+                (Ok(input), Ok(output)) if input == output
+            );
+
+        if same {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} can't be both the input and the output, as the output would overwrite it",
+                    output
+                ),
+            ));
+        }
+    }
+
+    let input = match input {
+        Some(file) => Reader::open(file)?,
+        None => Reader::default(),
+    };
+    let output = match output {
+        Some(file) => Writer::create(file)?,
+        None => Writer::default(),
+    };
+
+    Ok((input, output))
+}
+
+/// Read the bytes of an object file, decompressing it if it's gzipped
+fn read_object(file: &str) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+
+    // Directories can be opened on some platforms, and then fail part way through being read
+    if !fs::metadata(file)?.is_file() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unable to load {}, as it isn't a regular file", file),
+        ));
+    }
+
+    if file.ends_with(".gz") {
+        #[cfg(feature = "flate2")]
+        flate2::read::GzDecoder::new(File::open(file)?).read_to_end(&mut buffer)?;
+
+        #[cfg(not(feature = "flate2"))]
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Unable to load {}, as support for gzipped files wasn't built in (see the flate2 feature)",
+                file
+            ),
+        ));
+    } else {
+        File::open(file)?.read_to_end(&mut buffer)?;
+    }
+
+    let bom = byte_order_mark(&buffer);
+    buffer.drain(..bom);
+
+    Ok(buffer)
+}
+
+/// The length of a byte order mark some editors put at the start of a file, or 0 if there
+/// isn't one.
+///
+/// Only marks which can't be the start of a sensible object file are recognised: a UTF-8
+/// mark makes the file an odd length, and a big endian UTF-16 mark would load the program at
+/// xFEFF, among the device registers. A little endian UTF-16 mark is left alone, as xFFFE is
+/// the MCR, which an object file may well set.
+fn byte_order_mark(buffer: &[u8]) -> usize {
+    match buffer {
+        [0xEF, 0xBB, 0xBF, ..] if buffer.len() % 2 == 1 => 3,
+        [0xFE, 0xFF, _, _, ..] => 2,
+        _ => 0,
+    }
+}
+
+/// What a reserved instruction does, given the simulator and the instruction itself
+type Extension = dyn Fn(&mut Simulator, u16);
+
+/// What an instruction changed, so that it can be undone by
+/// [`step_back`](struct.Simulator.html#method.step_back)
+struct Delta {
+    registers: [u16; 8],
+    pc: u16,
+    ir: u16,
+    ir_address: u16,
+    psr: u16,
+    saved_ssp: u16,
+    saved_usp: u16,
+    clock: u16,
+    /// The address and previous value of each word stored to, in the order they were stored
+    memory: Vec<(u16, u16)>,
+}
+
 pub struct Simulator {
-    memory: [u16; 0xFFFF],
+    memory: Vec<u16>,
     registers: [u16; 8],
     pc: u16,
     ir: u16,
-    cc: usize,
+    psr: u16,
+    saved_ssp: u16,
+    saved_usp: u16,
     input: Reader,
     display: Writer,
     tracer: Tracer,
+    echo: bool,
+    breakpoints: HashSet<u16>,
+    region_watches: Vec<(u16, u16)>,
+    watches: HashMap<u16, WatchKind>,
+    breakpoint_list: Vec<BreakpointInfo>,
+    next_breakpoint_id: usize,
+    ir_address: u16,
+    fault_context: Option<(u16, String)>,
+    held_output: Option<Vec<u8>>,
+    strict_decoding: bool,
+    executed: Option<Vec<(u16, u16, u16)>>,
+    watch_hit: Option<WatchHit>,
+    register_watches: HashSet<usize>,
+    trap_base: u16,
+    entry_point: u16,
+    call_stack: Vec<u16>,
+    call_entries: Vec<u16>,
+    symbols: BTreeMap<u16, String>,
+    fetch_hook: Option<Box<dyn FnMut(u16, u16) -> u16>>,
+    extensions: HashMap<u16, Rc<Extension>>,
+    call_counts: HashMap<(u16, u16), u64>,
+    check_returns: bool,
+    warnings: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+    overflow_policy: OverflowPolicy,
+    trace_format: TraceFormat,
+    memory_trace: Option<(u16, u16)>,
+    pending_output: Option<u8>,
+    binary_output: bool,
+    display_failures: usize,
+    display_retries: usize,
+    quiet_halt: bool,
+    decimal_trap: bool,
+    builtin_traps: bool,
+    muted_until: Option<u16>,
+    instructions: u64,
+    touched: Option<(u16, u16)>,
+    trace_gate: Option<u16>,
+    check_encodings: bool,
+    check_next_store: bool,
+    check_trap_table: bool,
+    check_null_execution: bool,
+    eof_policy: EofPolicy,
+    original: HashMap<u16, u16>,
+    pending_input: VecDeque<u8>,
+    keyboard_ready: bool,
+    history: VecDeque<Delta>,
+    history_depth: usize,
+    interrupt_interval: u64,
+    ring_size: usize,
+    ring: VecDeque<String>,
+    faulted: bool,
+    halt_reason: Option<HaltReason>,
+    provenance: Option<Vec<(u16, u8)>>,
+    ddr_read: DdrRead,
+    access_counts: Option<Vec<u64>>,
+    stack_guard: Option<(u16, u16)>,
+    stack_pointer: usize,
+    stack_canary: Option<u16>,
+    check_devices: bool,
+    execution_counts: Vec<u64>,
+    last_output: u16,
+    metadata: Metadata,
+    formatter: Formatter,
+    predictors: HashMap<u16, (Predictor, u64, u64)>,
+    predictor_kind: PredictorKind,
+    global_history: u16,
+    shared_predictors: Vec<Predictor>,
+    trace_prediction: bool,
+    prediction: Option<(Predictor, Predictor, Branch)>,
+    last_branch: Option<Branch>,
+    paused: Option<HaltReason>,
+    cycle: Option<(usize, u16, [u16; 8])>,
+    cache: Option<Cache>,
+    device_latency: DeviceLatency,
+    io_wait_cycles: u64,
 }
 
 impl Simulator {
     #[must_use]
     pub fn new(input: Reader, display: Writer, tracer: Tracer) -> Self {
-        let mut memory = [0; 0xFFFF];
+        let mut memory = vec![0; 0x10000];
         memory[CLK] = 0x8000;
         memory[DSR] = 0x8000;
         Self {
@@ -58,122 +639,4662 @@ impl Simulator {
             registers: [0; 8],
             pc: 0,
             ir: 0,
-            cc: 0b010,
+            psr: 0b010,
+            saved_ssp: INITIAL_SSP,
+            saved_usp: INITIAL_USP,
             input,
             display,
             tracer,
+            echo: false,
+            breakpoints: HashSet::new(),
+            region_watches: Vec::new(),
+            watches: HashMap::new(),
+            breakpoint_list: Vec::new(),
+            next_breakpoint_id: 1,
+            ir_address: 0,
+            fault_context: None,
+            held_output: None,
+            strict_decoding: false,
+            executed: None,
+            watch_hit: None,
+            register_watches: HashSet::new(),
+            trap_base: 0x0000,
+            entry_point: 0x0000,
+            call_stack: Vec::new(),
+            call_entries: Vec::new(),
+            symbols: BTreeMap::new(),
+            fetch_hook: None,
+            extensions: HashMap::new(),
+            call_counts: HashMap::new(),
+            check_returns: false,
+            warnings: Vec::new(),
+            diagnostics: Vec::new(),
+            overflow_policy: OverflowPolicy::Error,
+            trace_format: TraceFormat::default(),
+            memory_trace: None,
+            pending_output: None,
+            binary_output: false,
+            display_failures: 0,
+            display_retries: DISPLAY_RETRIES,
+            quiet_halt: false,
+            decimal_trap: false,
+            builtin_traps: false,
+            muted_until: None,
+            instructions: 0,
+            touched: None,
+            trace_gate: None,
+            check_encodings: false,
+            check_next_store: false,
+            check_trap_table: false,
+            check_null_execution: false,
+            eof_policy: EofPolicy::default(),
+            original: HashMap::new(),
+            pending_input: VecDeque::new(),
+            keyboard_ready: false,
+            history: VecDeque::new(),
+            history_depth: 0,
+            interrupt_interval: INTERRUPT_CHECK_INTERVAL,
+            ring_size: 0,
+            ring: VecDeque::new(),
+            faulted: false,
+            halt_reason: None,
+            provenance: None,
+            ddr_read: DdrRead::default(),
+            access_counts: None,
+            stack_guard: None,
+            stack_pointer: 6,
+            stack_canary: None,
+            check_devices: false,
+            execution_counts: vec![0; 0x10000],
+            last_output: 0x0000,
+            metadata: Metadata::default(),
+            formatter: Formatter::default(),
+            predictors: HashMap::new(),
+            predictor_kind: PredictorKind::default(),
+            global_history: 0,
+            shared_predictors: Vec::new(),
+            trace_prediction: false,
+            prediction: None,
+            last_branch: None,
+            paused: None,
+            cycle: None,
+            cache: None,
+            device_latency: DeviceLatency::default(),
+            io_wait_cycles: 0,
+        }
+    }
+
+    /// Echo every character typed at the keyboard back to the display, as a cooked terminal
+    /// would.
+    ///
+    /// This is off by default, as GETC doesn't echo the character it reads. Input taken from a
+    /// file or buffer is never echoed, as nobody typed it.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::Cursor;
+    ///
+    /// let run = |input: Reader| {
+    ///     let mut simulator = Simulator::new(input, Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///         .with_echo(true)
+    ///         .load_source(
+    ///             0x3000,
+    ///             "LOOP    LDI R0, KBSR
+    ///                      BRzp LOOP
+    ///                      STI R1, MCR
+    ///              KBSR    .FILL xFE00
+    ///              MCR     .FILL xFFFE",
+    ///         )
+    ///         .unwrap();
+    ///
+    ///     simulator.execute();
+    ///     assert_eq!(simulator.memory()[0xFE02], u16::from(b'a'));
+    ///     simulator.display().contents().to_vec()
+    /// };
+    ///
+    /// // A custom reader stands in for someone typing, so what it gives is echoed
+    /// assert_eq!(run(Reader::Custom(Box::new(Cursor::new(b"a".to_vec())))), b"a");
+    /// assert!(run(Reader::from("a")).is_empty());
+    /// ```
+    #[must_use]
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// Discard anything written to the display while the HALT trap (x25) is running, so that
+    /// captured output only contains what the program itself printed, and not the operating
+    /// system's halting banner.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// // Print 'A', then HALT, with a HALT routine at x3003 which prints '!' before stopping
+    /// // the clock
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R0, A
+    ///              STI R0, DDR
+    ///              HALT
+    ///              LD R0, BANG
+    ///              STI R0, DDR
+    ///              STI R1, MCR
+    ///      A       .FILL x41
+    ///      BANG    .FILL x21
+    ///      DDR     .FILL xFE06
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_quiet_halt(true);
+    ///
+    /// simulator.write(0x0025, 0x3003);
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.display().contents(), b"A");
+    /// ```
+    #[must_use]
+    pub fn with_quiet_halt(mut self, quiet: bool) -> Self {
+        self.quiet_halt = quiet;
+        self
+    }
+
+    /// Handle `TRAP x31` in the simulator itself, printing R0 as a signed decimal number
+    /// followed by a newline, without needing a routine for it in the operating system.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "
+    ///              AND R0, R0, #0
+    ///              ADD R0, R0, #-1
+    ///              TRAP x31
+    ///              AND R1, R1, #0
+    ///              STI R1, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_binary_output(true)
+    /// .with_decimal_trap(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.display().contents(), b"-1\n");
+    /// ```
+    #[must_use]
+    pub fn with_decimal_trap(mut self, enable: bool) -> Self {
+        self.decimal_trap = enable;
+        self
+    }
+
+    /// Handle the standard traps (GETC, OUT, PUTS, IN, PUTSP, and HALT) in the simulator
+    /// itself whenever the trap vector table has nothing for them, so that a program can be
+    /// run without loading an operating system.
+    ///
+    /// GETC and IN wait for a key when reading from the keyboard. If the machine stops while
+    /// they're waiting, such as when an input file runs out, the PC is left on the TRAP so
+    /// that it's the instruction which asked for the input.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Reader, Simulator, Tracer, Writer};
+    ///
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from("a"),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .load_source(
+    ///     0x3000,
+    ///     "        GETC
+    ///              ADD R0, R0, #1
+    ///              OUT
+    ///              LEA R0, DONE
+    ///              PUTS
+    ///              HALT
+    ///      DONE    .STRINGZ \"!\"",
+    /// )
+    /// .unwrap()
+    /// .with_binary_output(true)
+    /// .with_quiet_halt(true)
+    /// .with_builtin_traps(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::Halted));
+    /// assert_eq!(simulator.display().contents(), b"b!");
+    /// ```
+    ///
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        GETC
+    ///              GETC",
+    /// )
+    /// .unwrap()
+    /// .with_builtin_traps(true);
+    ///
+    /// simulator.set_pending_input(b'a');
+    /// simulator.step();
+    /// assert_eq!(simulator.registers()[0], u16::from(b'a'));
+    ///
+    /// assert!(!simulator.step().running);
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::OutOfInput));
+    /// assert_eq!(simulator.pc(), 0x3001);
+    /// ```
+    #[must_use]
+    pub fn with_builtin_traps(mut self, enable: bool) -> Self {
+        self.builtin_traps = enable;
+        self
+    }
+
+    /// Check the input for ESC every `interval` instructions (1000 by default), so that the
+    /// simulator can be stopped even when the program never asks for input. Anything else
+    /// typed is kept for when the program does ask. An interval of 0 disables the check.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::{Error, ErrorKind, Read};
+    ///
+    /// // Input which reports that ESC has been pressed
+    /// struct Escape;
+    ///
+    /// impl Read for Escape {
+    ///     fn read(&mut self, _: &mut [u8]) -> Result<usize, Error> {
+    ///         Err(Error::new(ErrorKind::Interrupted, ""))
+    ///     }
+    /// }
+    ///
+    /// // Write to the DDR forever, never polling the KBSR
+    /// let mut simulator = Simulator::new(
+    ///     Reader::Custom(Box::new(Escape)),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .with_interrupt_check(10)
+    /// .load_source(
+    ///     0x3000,
+    ///     "LOOP    STI R0, DDR
+    ///              BRnzp LOOP
+    ///      DDR     .FILL xFE06",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert!(!simulator.is_running());
+    /// assert_eq!(simulator.instruction_count(), 10);
+    /// ```
+    #[must_use]
+    pub fn with_interrupt_check(mut self, interval: u64) -> Self {
+        self.interrupt_interval = interval;
+        self
+    }
+
+    /// Fill every register with pseudo-random values generated from `seed`, so that a program
+    /// which assumes they start at zero is caught out. The same seed always gives the same
+    /// values.
+    ///
+    /// R6 and R7 are scrambled as well, so a program has to set up its own stack pointer in
+    /// R6, and R7 only holds a return address once a JSR, JSRR, or TRAP has set it. The
+    /// condition code isn't changed.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let simulator = |seed| {
+    ///     Simulator::from_source(0x3000, "HALT")
+    ///         .unwrap()
+    ///         .with_randomized_registers(seed)
+    /// };
+    ///
+    /// assert_eq!(simulator(42).registers(), simulator(42).registers());
+    /// assert_ne!(simulator(42).registers(), simulator(43).registers());
+    /// assert_ne!(simulator(42).registers(), [0; 8]);
+    /// ```
+    #[must_use]
+    pub fn with_randomized_registers(mut self, seed: u64) -> Self {
+        // SplitMix64, which is plenty for scrambling eight registers
+        let mut state = seed;
+        for register in self.registers.iter_mut() {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            *register = (z ^ (z >> 31)) as u16;
+        }
+        self
+    }
+
+    /// Start with the condition code set to `condition_code` rather than Z, such as to test
+    /// how a branch at the start of a program behaves.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{ConditionCode, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        BRn NEGATIVE
+    ///              ADD R0, R0, #1
+    ///      NEGATIVE ADD R1, R1, #1",
+    /// )
+    /// .unwrap()
+    /// .with_condition_code(ConditionCode::Negative);
+    ///
+    /// simulator.step();
+    /// assert_eq!(simulator.pc(), 0x3002);
+    /// ```
+    #[must_use]
+    pub fn with_condition_code(mut self, condition_code: ConditionCode) -> Self {
+        self.psr = self.psr & !0b111 | condition_code.bits();
+        self
+    }
+
+    /// Choose what a program sees when it reads the DDR. Whether the display is ready is
+    /// still only reported by the DSR.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{DdrRead, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LDI R0, DDR
+    ///              LDI R1, DSR
+    ///              AND R2, R2, #0
+    ///              STI R2, MCR
+    ///      DSR     .FILL xFE04
+    ///      DDR     .FILL xFE06
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_ddr_read(DdrRead::Fixed(0x00FF));
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.registers()[0], 0x00FF);
+    /// assert_eq!(simulator.registers()[1], 0x8000);
+    /// ```
+    #[must_use]
+    pub fn with_ddr_read(mut self, ddr_read: DdrRead) -> Self {
+        self.ddr_read = ddr_read;
+        self
+    }
+
+    /// Choose what happens when the program asks for more input than was provided.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{EofPolicy, HaltReason, Reader, Simulator, Tracer, Writer};
+    ///
+    /// // Read and print two characters
+    /// let run = |policy: EofPolicy| {
+    ///     let mut simulator = Simulator::new(
+    ///         Reader::from("a"),
+    ///         Writer::Buffer(Vec::new()),
+    ///         Tracer::NoTrace,
+    ///     )
+    ///     .with_eof_policy(policy)
+    ///     .load_source(
+    ///         0x3000,
+    ///         "LOOP    LDI R0, KBSR
+    ///                  BRzp LOOP
+    ///                  LDI R0, KBDR
+    ///                  STI R0, DDR
+    ///                  ADD R1, R1, #1
+    ///                  ADD R2, R1, #-2
+    ///                  BRn LOOP
+    ///                  STI R3, MCR
+    ///          KBSR    .FILL xFE00
+    ///          KBDR    .FILL xFE02
+    ///          DDR     .FILL xFE06
+    ///          MCR     .FILL xFFFE",
+    ///     )
+    ///     .unwrap();
+    ///
+    ///     simulator.execute();
+    ///     simulator
+    /// };
+    ///
+    /// let simulator = run(EofPolicy::Sentinel(0x04));
+    /// assert_eq!(simulator.display().contents(), b"a\x04");
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::Halted));
+    ///
+    /// // Nothing more can arrive from a buffer, so there's no point waiting for it
+    /// let simulator = run(EofPolicy::Block);
+    /// assert_eq!(simulator.display().contents(), b"a");
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::OutOfInput));
+    /// ```
+    #[must_use]
+    pub fn with_eof_policy(mut self, policy: EofPolicy) -> Self {
+        self.eof_policy = policy;
+        self
+    }
+
+    /// Warn whenever a register mode ADD or AND with bits 4 and 3 set is executed. Unlike the
+    /// [encoding check](#method.with_encoding_check), this only looks at instructions which
+    /// are actually executed, including any the program wrote itself. Either way, the
+    /// instruction still executes as though the bits were clear.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let run = |strict| {
+    ///     let mut simulator = Simulator::from_source(
+    ///         0x3000,
+    ///         "        ADD R0, R0, #2
+    ///                  .FILL x1218     ; ADD R1, R0, R0 with bits 4 and 3 set
+    ///                  AND R2, R2, #0
+    ///                  STI R2, MCR
+    ///          MCR     .FILL xFFFE",
+    ///     )
+    ///     .unwrap()
+    ///     .with_strict_decoding(strict);
+    ///
+    ///     simulator.execute();
+    ///     simulator
+    /// };
+    ///
+    /// let strict = run(true);
+    /// assert_eq!(strict.registers()[1], 4);
+    /// assert_eq!(
+    ///     strict.warnings(),
+    ///     ["ADD R1, R0, R0 at 0x3001 (0x1218) has bits 4 and 3 set in register mode"]
+    /// );
+    ///
+    /// let normal = run(false);
+    /// assert_eq!(normal.registers()[1], 4);
+    /// assert!(normal.warnings().is_empty());
+    /// ```
+    #[must_use]
+    pub fn with_strict_decoding(mut self, strict: bool) -> Self {
+        self.strict_decoding = strict;
+        self
+    }
+
+    /// Warn about any loaded word whose unused bits aren't set to their expected value (such as
+    /// bits 4 and 3 of a register mode ADD), which usually points to a buggy assembler.
+    ///
+    /// Every loaded word is checked, so data which happens to look like such an instruction is
+    /// also reported.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let simulator = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .with_encoding_check(true)
+    ///     .load_source(
+    ///         0x3000,
+    ///         "        ADD R1, R0, R0
+    ///                  .FILL x1218     ; ADD R1, R0, R0 with bits 4 and 3 set",
+    ///     )
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     simulator.warnings(),
+    ///     ["Word 0x1218 at 0x3001 has unused bits set (expected 0x1200 for ADD R1, R0, R0)"]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_encoding_check(mut self, check: bool) -> Self {
+        self.check_encodings = check;
+        self
+    }
+
+    /// Write each byte the program sends to the display as is, rather than as text with a
+    /// carriage return added before each newline, for programs whose output is binary data.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let run = |binary: bool| {
+    ///     // Write a newline to the DDR, then stop the clock
+    ///     let mut simulator = Simulator::from_source(
+    ///         0x3000,
+    ///         "        LD R1, NEWLINE
+    ///                  STI R1, DDR
+    ///                  STI R2, MCR
+    ///          DDR     .FILL xFE06
+    ///          MCR     .FILL xFFFE
+    ///          NEWLINE .FILL #10",
+    ///     )
+    ///     .unwrap()
+    ///     .with_binary_output(binary);
+    ///
+    ///     simulator.execute();
+    ///     simulator.display().contents().to_vec()
+    /// };
+    ///
+    /// assert_eq!(run(false), b"\r\n");
+    /// assert_eq!(run(true), b"\n");
+    /// ```
+    #[must_use]
+    pub fn with_binary_output(mut self, binary: bool) -> Self {
+        self.binary_output = binary;
+        self
+    }
+
+    /// Let writing to the display fail `retries` times in a row (16 by default) before
+    /// stopping the machine. A character which fails to display leaves the display not ready,
+    /// and is retried whenever the program next polls the DSR, so a transient failure doesn't
+    /// stop all further output.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::cell::RefCell;
+    /// use std::io::{Error, ErrorKind, Write};
+    /// use std::rc::Rc;
+    ///
+    /// // A display that fails the first time it's written to
+    /// struct Flaky(bool, Rc<RefCell<Vec<u8>>>);
+    ///
+    /// impl Write for Flaky {
+    ///     fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+    ///         if std::mem::replace(&mut self.0, false) {
+    ///             return Err(Error::new(ErrorKind::Other, "display unavailable"));
+    ///         }
+    ///         self.1.borrow_mut().write(buf)
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> Result<(), Error> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let output = Rc::new(RefCell::new(Vec::new()));
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Custom(Box::new(Flaky(true, Rc::clone(&output)))),
+    ///     Tracer::NoTrace,
+    /// )
+    /// // Write 'A' to the DDR, wait for the DSR to be ready, then write 'B'
+    /// .load_source(
+    ///     0x3000,
+    ///     "        LD R0, CHAR
+    ///              STI R0, DDR
+    ///      WAIT    LDI R1, DSR
+    ///              BRzp WAIT
+    ///              ADD R0, R0, #1
+    ///              STI R0, DDR
+    ///              STI R2, MCR
+    ///      CHAR    .FILL x41
+    ///      DDR     .FILL xFE06
+    ///      DSR     .FILL xFE04
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(*output.borrow(), b"AB");
+    /// assert!(simulator.warnings().is_empty());
+    /// ```
+    ///
+    /// Without any retries the first failure stops the machine.
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::{Error, ErrorKind, Write};
+    ///
+    /// struct Broken;
+    ///
+    /// impl Write for Broken {
+    ///     fn write(&mut self, _: &[u8]) -> Result<usize, Error> {
+    ///         Err(Error::new(ErrorKind::Other, "display unavailable"))
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> Result<(), Error> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Custom(Box::new(Broken)),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .with_display_retries(0)
+    /// // Write 'A' to the DDR
+    /// .load_source(
+    ///     0x3000,
+    ///     "        LD R0, CHAR
+    ///              STI R0, DDR
+    ///              HALT
+    ///      CHAR    .FILL x41
+    ///      DDR     .FILL xFE06",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.warnings(), ["Unable to write to the display: display unavailable"]);
+    /// ```
+    #[must_use]
+    pub fn with_display_retries(mut self, retries: usize) -> Self {
+        self.display_retries = retries;
+        self
+    }
+
+    /// Pass every load and store (but not instruction fetches) through a data cache, keeping
+    /// track of hits, misses, and the cycles spent waiting on memory.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{CacheConfig, Simulator};
+    ///
+    /// // Load each of the 16 words from x4010 in turn, then stop the clock
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R2, DATA
+    ///              AND R1, R1, #0
+    ///              ADD R1, R1, #15
+    ///              ADD R1, R1, #1
+    ///      LOOP    LDR R0, R2, #0
+    ///              ADD R2, R2, #1
+    ///              ADD R1, R1, #-1
+    ///              BRp LOOP
+    ///              STI R1, MCR
+    ///      DATA    .FILL x4010
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_cache(CacheConfig {
+    ///     size: 64,
+    ///     line_size: 4,
+    ///     associativity: 1,
+    ///     hit_latency: 1,
+    ///     miss_latency: 10,
+    /// });
+    ///
+    /// simulator.execute();
+    ///
+    /// // One miss for every 4 word line, plus one for the line holding DATA, which the STI's
+    /// // pointer to the clock shares
+    /// let stats = simulator.cache_stats().unwrap();
+    /// assert_eq!((stats.hits, stats.misses), (13, 5));
+    /// assert_eq!(stats.stall_cycles, 5 * 9);
+    /// ```
+    ///
+    /// # Panics
+    /// Will panic if the cache's size isn't a whole number of sets of lines
+    #[must_use]
+    pub fn with_cache(mut self, config: CacheConfig) -> Self {
+        self.cache = Some(Cache::new(config));
+        self
+    }
+
+    /// How the data cache has performed, if there is one
+    #[must_use]
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(Cache::stats)
+    }
+
+    /// Stall for `latency` cycles each time the program polls the KBSR or DSR, so that the
+    /// [cycle count](#method.cycles) of a program which waits on its devices reflects it.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{DeviceLatency, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R1, CHAR
+    ///      FIRST   LDI R2, DSR
+    ///              BRzp FIRST
+    ///              STI R1, DDR
+    ///      SECOND  LDI R2, DSR
+    ///              BRzp SECOND
+    ///              STI R1, DDR
+    ///              AND R0, R0, #0
+    ///              STI R0, MCR
+    ///      CHAR    .FILL x0041
+    ///      DSR     .FILL xFE04
+    ///      DDR     .FILL xFE06
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_device_latency(DeviceLatency {
+    ///     keyboard: 0,
+    ///     display: 20,
+    /// });
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.io_wait_cycles(), 2 * 20);
+    /// assert_eq!(simulator.cycles(), simulator.instruction_count() + 2 * 20);
+    /// ```
+    #[must_use]
+    pub fn with_device_latency(mut self, latency: DeviceLatency) -> Self {
+        self.device_latency = latency;
+        self
+    }
+
+    /// How many cycles have been spent waiting on the keyboard and display
+    #[must_use]
+    pub fn io_wait_cycles(&self) -> u64 {
+        self.io_wait_cycles
+    }
+
+    /// How many cycles the program has taken, counting one for each instruction, plus any
+    /// stalls waiting on the data cache or the devices.
+    #[must_use]
+    pub fn cycles(&self) -> u64 {
+        self.instructions
+            + self
+                .cache
+                .as_ref()
+                .map_or(0, |cache| cache.stats().stall_cycles)
+            + self.io_wait_cycles
+    }
+
+    /// Look up trap routines in a table starting at `base` instead of x0000, for operating
+    /// systems which put their trap vector table somewhere else.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(0x3000, "TRAP x25")
+    ///     .unwrap()
+    ///     .with_trap_base(0x0100);
+    ///
+    /// simulator.write(0x0025, 0x0300);
+    /// simulator.write(0x0125, 0x0400);
+    /// simulator.step();
+    ///
+    /// assert_eq!(simulator.pc(), 0x0400);
+    /// ```
+    #[must_use]
+    pub fn with_trap_base(mut self, base: u16) -> Self {
+        self.trap_base = base;
+        self
+    }
+
+    /// Stop the machine if a store overwrites the instruction that's about to be fetched,
+    /// which is almost always a bug rather than deliberate self modifying code.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// // The ST overwrites the ADD straight after it
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ST R1, NEXT
+    ///      NEXT    ADD R1, R1, #1
+    ///              STI R2, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_next_store_check(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert!(!simulator.is_running());
+    /// assert_eq!(simulator.pc(), 0x3001);
+    /// assert_eq!(
+    ///     simulator.warnings(),
+    ///     ["Store at 0x3000 overwrote the next instruction to execute, at 0x3001"]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_next_store_check(mut self, check: bool) -> Self {
+        self.check_next_store = check;
+        self
+    }
+
+    /// Stop the machine if user code (anything running from x3000 up) stores into the trap
+    /// vector table, wherever [`with_trap_base`](#method.with_trap_base) puts it, which is
+    /// usually a bug or an attempt to hijack a system call.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        STI R1, VECTOR  ; Replace the HALT routine
+    ///              AND R1, R1, #0
+    ///              STI R1, MCR
+    ///      VECTOR  .FILL x0025
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_trap_table_check(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.pc(), 0x3001);
+    /// assert_eq!(
+    ///     simulator.warnings(),
+    ///     ["User code at 0x3000 stored to 0x0025, in the trap vector table"]
+    /// );
+    ///
+    /// // A table which has been moved is checked where it is instead
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        STI R1, VECTOR
+    ///              AND R1, R1, #0
+    ///              STI R1, MCR
+    ///      VECTOR  .FILL x0125
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_trap_base(0x0100)
+    /// .with_trap_table_check(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.pc(), 0x3001);
+    /// ```
+    #[must_use]
+    pub fn with_trap_table_check(mut self, check: bool) -> Self {
+        self.check_trap_table = check;
+        self
+    }
+
+    /// Stop the machine if user code sends the PC into the trap vector table (x0000 to x00FF,
+    /// unless [`with_trap_base`](#method.with_trap_base) moves it), which is almost always a
+    /// wild jump, before the table is executed as code.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        AND R1, R1, #0
+    ///              JMP R1",
+    /// )
+    /// .unwrap()
+    /// .with_null_execution_check(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.pc(), 0x0000);
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::NullExecution));
+    /// assert_eq!(
+    ///     simulator.warnings(),
+    ///     ["JMP R1 at 0x3001 sent the PC to 0x0000, in the trap vector table"]
+    /// );
+    ///
+    /// // A table which has been moved is checked where it is instead
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R1, TABLE
+    ///              JMP R1
+    ///      TABLE   .FILL x0180",
+    /// )
+    /// .unwrap()
+    /// .with_trap_base(0x0100)
+    /// .with_null_execution_check(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::NullExecution));
+    /// ```
+    #[must_use]
+    pub fn with_null_execution_check(mut self, check: bool) -> Self {
+        self.check_null_execution = check;
+        self
+    }
+
+    /// Warn whenever an LDR or STR relative to the stack pointer accesses anything outside of
+    /// `start` to `end` (inclusive), such as when a program pops more than it pushed.
+    #[must_use]
+    pub fn with_stack_guard(mut self, start: u16, end: u16) -> Self {
+        self.stack_guard = Some((start, end));
+        self
+    }
+
+    /// Choose which register the stack guard treats as the stack pointer, for calling
+    /// conventions which don't use R6.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R5, STACK
+    ///              LD R6, OTHER
+    ///              STR R0, R6, #0  ; Fine, as R6 isn't the stack pointer
+    ///              STR R0, R5, #-1 ; Just past the top of the stack
+    ///              AND R0, R0, #0
+    ///              STI R0, MCR
+    ///      STACK   .FILL x4000
+    ///      OTHER   .FILL x6000
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_stack_guard(0x4000, 0x40FF)
+    /// .with_stack_pointer(5);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(
+    ///     simulator.warnings(),
+    ///     ["STR R0, R5, #-1 at 0x3003 accessed 0x3FFF, outside the stack (0x4000 to 0x40FF)"]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_stack_pointer(mut self, register: usize) -> Self {
+        self.stack_pointer = register;
+        self
+    }
+
+    /// Put `value` at `address`, just past the end of the stack, and stop the machine if
+    /// anything is ever stored over it, such as when a program pushes more than the stack
+    /// has room for.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R6, STACK
+    ///              ADD R6, R6, #-1
+    ///              STR R0, R6, #0
+    ///              ADD R6, R6, #-1
+    ///              STR R0, R6, #0  ; One push too many
+    ///              AND R0, R0, #0
+    ///              STI R0, MCR
+    ///      STACK   .FILL x4000
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_stack_canary(0x3FFE, 0xC0DE);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(
+    ///     simulator.halt_reason(),
+    ///     Some(HaltReason::Fault(String::from(
+    ///         "STR R0, R6, #0 at 0x3004 overwrote the stack canary at 0x3FFE"
+    ///     )))
+    /// );
+    /// assert_eq!(simulator.fault_context().unwrap().0, 0x3004);
+    /// ```
+    #[must_use]
+    pub fn with_stack_canary(mut self, address: u16, value: u16) -> Self {
+        self.memory[address as usize] = value;
+        self.stack_canary = Some(address);
+        self
+    }
+
+    /// Remember what each of the last `depth` instructions changed, so that they can be
+    /// undone with [`step_back`](#method.step_back).
+    ///
+    /// Stores to the keyboard and display registers are never undone, since the input they
+    /// took and the output they gave can't be taken back. Statistics such as instruction
+    /// counts aren't wound back either.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        AND R0, R0, #0
+    ///              ADD R0, R0, #7
+    ///              ST R0, VALUE
+    ///              STI R0, MCR
+    ///      VALUE   .FILL #1
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_history(2);
+    ///
+    /// simulator.execute();
+    /// assert!(!simulator.is_running());
+    /// assert_eq!(simulator.memory()[0x3004], 7);
+    ///
+    /// // Undoing the STI to the MCR starts the clock again
+    /// assert!(simulator.step_back());
+    /// assert!(simulator.is_running());
+    /// assert_eq!(simulator.pc(), 0x3003);
+    ///
+    /// assert!(simulator.step_back());
+    /// assert_eq!(simulator.memory()[0x3004], 1);
+    /// assert_eq!(simulator.pc(), 0x3002);
+    /// assert_eq!(simulator.registers()[0], 7);
+    ///
+    /// // Only the last two instructions were kept
+    /// assert!(!simulator.step_back());
+    ///
+    /// simulator.execute();
+    /// assert_eq!(simulator.memory()[0x3004], 7);
+    /// ```
+    #[must_use]
+    pub fn with_history(mut self, depth: usize) -> Self {
+        self.history_depth = depth;
+        self.history = VecDeque::with_capacity(depth);
+        self
+    }
+
+    /// Pass every instruction through `hook` as it's fetched, with the address it was fetched
+    /// from, and execute whatever the hook returns instead. Memory itself is left alone, so
+    /// this can instrument or patch a program without changing it.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R0, R0, #5
+    ///              ADD R0, R0, #3
+    ///              AND R1, R1, #0
+    ///              STI R1, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// // Turn every ADD into a NOP (a branch which is never taken)
+    /// .with_fetch_hook(|_, ir| if ir >> 12 == 0x1 { 0x0000 } else { ir });
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.registers()[0], 0);
+    /// assert_eq!(simulator.memory()[0x3000], 0x1025);
+    /// ```
+    #[must_use]
+    pub fn with_fetch_hook<F: FnMut(u16, u16) -> u16 + 'static>(mut self, hook: F) -> Self {
+        self.fetch_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Warn whenever a program uses a device register in a way that makes no sense, such as
+    /// writing to the KBDR, reading the DDR, or setting any bit of the KBSR or DSR other than
+    /// the interrupt enable bit.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        STI R0, KBDR
+    ///              STI R0, MCR
+    ///      KBDR    .FILL xFE02
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_device_check(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(
+    ///     simulator.warnings(),
+    ///     ["STI R0, #1 at 0x3000 wrote to the KBDR, which is read only"]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_device_check(mut self, check: bool) -> Self {
+        self.check_devices = check;
+        self
+    }
+
+    /// Warn whenever a RET (JMP R7) doesn't return to the address saved by the matching JSR, JSRR, or TRAP.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// // Call a subroutine that increments R7 before returning, which skips the NOP after the
+    /// // JSR and lands on the instruction that stops the clock.
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        JSR SUB
+    ///              NOP
+    ///              STI R3, MCR
+    ///      SUB     ADD R7, R7, #1
+    ///              RET
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_return_check(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(
+    ///     simulator.warnings(),
+    ///     ["RET at 0x3004 returned to 0x3002, but the matching call returns to 0x3001"]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_return_check(mut self, check: bool) -> Self {
+        self.check_returns = check;
+        self
+    }
+
+    /// Any warnings raised while the program was running
+    #[must_use]
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Every warning and fault raised so far, with the address each is about and a short code
+    /// naming its kind.
+    ///
+    /// Messages about the simulator rather than the program, such as ESC being pressed or the
+    /// program running out of input, go through the `log` facade instead, so that an
+    /// application embedding the simulator can capture or filter them. Without a logger
+    /// installed they're printed to stderr.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Diagnostic, Severity, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "
+    ///         .FILL x1258     ; ADD R1, R1, R0 with bits 4 and 3 set
+    ///         AND R1, R1, #0
+    ///         STR R0, R1, #5  ; Clobbers the vector for TRAP x05
+    ///     ",
+    /// )
+    /// .unwrap()
+    /// .with_strict_decoding(true)
+    /// .with_trap_table_check(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// let diagnostics = simulator.diagnostics();
+    /// assert_eq!(diagnostics.len(), 2);
+    /// assert_eq!(
+    ///     (diagnostics[0].address, diagnostics[0].severity, diagnostics[0].code),
+    ///     (0x3000, Severity::Warning, "reserved-bits")
+    /// );
+    /// assert_eq!(
+    ///     diagnostics[1],
+    ///     Diagnostic {
+    ///         address: 0x3002,
+    ///         severity: Severity::Error,
+    ///         code: "trap-table-store",
+    ///         message: String::from("User code at 0x3002 stored to 0x0005, in the trap vector table"),
+    ///     }
+    /// );
+    /// assert!(simulator
+    ///     .diagnostics_json()
+    ///     .starts_with(r#"[{"address":12288,"severity":"warning","code":"reserved-bits","#));
+    /// ```
+    ///
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Simulator};
+    /// use log::{Level, Log, Metadata, Record};
+    /// use std::sync::Mutex;
+    ///
+    /// struct Capture(Mutex<Vec<(Level, String)>>);
+    ///
+    /// impl Log for Capture {
+    ///     fn enabled(&self, _: &Metadata) -> bool {
+    ///         true
+    ///     }
+    ///
+    ///     fn log(&self, record: &Record) {
+    ///         let message = record.args().to_string();
+    ///         self.0.lock().unwrap().push((record.level(), message));
+    ///     }
+    ///
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// static LOGGER: Capture = Capture(Mutex::new(Vec::new()));
+    /// log::set_logger(&LOGGER).unwrap();
+    /// log::set_max_level(log::LevelFilter::Info);
+    ///
+    /// // Poll the KBSR, which there's no input for
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LDI R0, KBSR
+    ///      KBSR    .FILL xFE00",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::OutOfInput));
+    /// assert_eq!(
+    ///     *LOGGER.0.lock().unwrap(),
+    ///     [(
+    ///         Level::Warn,
+    ///         String::from("Program requires more input than provided in the input file")
+    ///     )]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// The [diagnostics](#method.diagnostics) as a JSON array, for editors and other tools.
+    #[must_use]
+    pub fn diagnostics_json(&self) -> String {
+        serde_json::to_string(&self.diagnostics).unwrap()
+    }
+
+    /// The return addresses of every subroutine (or trap) currently being executed, innermost last
+    #[must_use]
+    pub fn backtrace(&self) -> &[u16] {
+        &self.call_stack
+    }
+
+    /// Which subroutines (or traps) called which, and how many times, as (caller, callee,
+    /// count), with each subroutine named by its entry address. Calls made outside of any
+    /// subroutine are from the program's entry point.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        JSR B
+    ///              JSR B
+    ///              AND R0, R0, #0
+    ///              STI R0, MCR
+    ///      MCR     .FILL xFFFE
+    ///
+    ///      B       ADD R1, R1, #1
+    ///              ADD R2, R1, #-1
+    ///              BRnp DONE       ; Only call C the first time through
+    ///              ADD R5, R7, #0
+    ///              JSR C
+    ///              ADD R7, R5, #0
+    ///      DONE    RET
+    ///
+    ///      C       RET",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.call_graph(), [(0x3000, 0x3005, 2), (0x3005, 0x300C, 1)]);
+    /// assert!(simulator
+    ///     .call_graph_dot()
+    ///     .contains("\"0x3000\" -> \"0x3005\" [label=\"2\"];"));
+    /// ```
+    #[must_use]
+    pub fn call_graph(&self) -> Vec<(u16, u16, u64)> {
+        let mut edges = self
+            .call_counts
+            .iter()
+            .map(|(&(caller, callee), &count)| (caller, callee, count))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+    }
+
+    /// The [call graph](#method.call_graph) in Graphviz's DOT format, with each edge labelled
+    /// by how many calls were made along it.
+    #[must_use]
+    pub fn call_graph_dot(&self) -> String {
+        let edges = self
+            .call_graph()
+            .into_iter()
+            .map(|(caller, callee, count)| {
+                format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                    self.formatter.hex(caller),
+                    self.formatter.hex(callee),
+                    count
+                )
+            })
+            .collect::<String>();
+
+        format!("digraph calls {{\n{}}}\n", edges)
+    }
+
+    /// The words from `start` to `end` (inclusive) as a loadable object file, with `start` as its
+    /// origin.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// // Store 1 through 5 into x4000 through x4004
+    /// let mut original = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R2, DATA
+    ///              AND R1, R1, #0
+    ///              ADD R3, R1, #5
+    ///      LOOP    ADD R1, R1, #1
+    ///              STR R1, R2, #0
+    ///              ADD R2, R2, #1
+    ///              ADD R3, R3, #-1
+    ///              BRp LOOP
+    ///              STI R3, MCR
+    ///      DATA    .FILL x4000
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    /// original.execute();
+    ///
+    /// let dump = std::env::temp_dir().join(format!("lc3sim_dump_{}.obj", std::process::id()));
+    /// std::fs::write(&dump, original.export_obj(0x4000, 0x4004)).unwrap();
+    ///
+    /// let copy = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .load(dump.to_str().unwrap())
+    ///     .unwrap();
+    /// assert_eq!(copy.memory()[0x4000..=0x4004], [1, 2, 3, 4, 5]);
+    /// assert_eq!(copy.memory()[0x4000..=0x4004], original.memory()[0x4000..=0x4004]);
+    ///
+    /// // The very top of memory can be exported too
+    /// assert_eq!(original.export_obj(0xFFFF, 0xFFFF), [0xFF, 0xFF, 0x00, 0x00]);
+    /// ```
+    ///
+    /// # Panics
+    /// Will panic if `end` is before `start`
+    #[must_use]
+    pub fn export_obj(&self, start: u16, end: u16) -> Vec<u8> {
+        std::iter::once(start)
+            .chain(self.memory[start as usize..=end as usize].iter().copied())
+            .flat_map(|word| vec![(word >> 8) as u8, word as u8])
+            .collect()
+    }
+
+    /// Record every instruction executed, for [`executed`](#method.executed) and
+    /// [`export_replay`](#method.export_replay).
+    #[must_use]
+    pub fn with_execution_recording(mut self, record: bool) -> Self {
+        self.executed = if record { Some(Vec::new()) } else { None };
+        self
+    }
+
+    /// The address and word of every instruction executed so far, in order, if they're being
+    /// recorded
+    #[must_use]
+    pub fn executed(&self) -> Vec<(u16, u16)> {
+        self.executed
+            .iter()
+            .flatten()
+            .map(|&(address, word, _)| (address, word))
+            .collect()
+    }
+
+    /// The recorded instructions flattened into a straight line program starting at
+    /// `origin`, as an object file, which replays what the original run did.
+    ///
+    /// This is a best effort: branches and jumps are dropped, since the replay just carries
+    /// on to the next instruction that was executed. Every load (including LEA) instead loads
+    /// the value the original run got, and JSR, JSRR, and TRAP just set R7, so the replay
+    /// needs neither the original data nor any input. Stores go to the addresses the original
+    /// run stored to, and self modifying code is replayed as it was executed. Condition codes
+    /// may differ, but nothing in the replay depends on them.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let mut original = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LEA R1, TEXT
+    ///      LOOP    LDR R0, R1, #0
+    ///              BRz DONE
+    ///              STI R0, DDR
+    ///              ADD R1, R1, #1
+    ///              BRnzp LOOP
+    ///      DONE    STI R0, MCR
+    ///      TEXT    .STRINGZ \"Hi\"
+    ///      DDR     .FILL xFE06
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_execution_recording(true);
+    ///
+    /// original.execute();
+    /// assert_eq!(original.executed()[..2], [(0x3000, 0xE206), (0x3001, 0x6040)]);
+    ///
+    /// let replay = std::env::temp_dir().join(format!("lc3sim_replay_{}.obj", std::process::id()));
+    /// std::fs::write(&replay, original.export_replay(0x5000)).unwrap();
+    ///
+    /// let mut replayed = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .load(replay.to_str().unwrap())
+    ///     .unwrap();
+    /// replayed.execute();
+    ///
+    /// assert_eq!(original.display().contents(), b"Hi");
+    /// assert_eq!(replayed.display().contents(), original.display().contents());
+    /// ```
+    #[must_use]
+    pub fn export_replay(&self, origin: u16) -> Vec<u8> {
+        // A value is kept inline, after an instruction using it and a branch over it
+        let with_value = |instruction, value| {
+            vec![
+                u16::from(instruction),
+                u16::from(Instruction::Branch(true, true, true, 1)),
+                value,
+            ]
+        };
+
+        std::iter::once(origin)
+            .chain(
+                self.executed.iter().flatten().flat_map(
+                    |&(_, word, value)| match Instruction::from(word) {
+                        Instruction::Add(..) | Instruction::And(..) | Instruction::Not(..) => {
+                            vec![word]
+                        }
+                        Instruction::Load(dr, _)
+                        | Instruction::LoadRelative(dr, _, _)
+                        | Instruction::LoadIndirect(dr, _)
+                        | Instruction::LoadEffectiveAddress(dr, _) => {
+                            with_value(Instruction::Load(dr, 1), value)
+                        }
+                        Instruction::JumpSubroutine(_)
+                        | Instruction::JumpSubroutineRegister(_)
+                        | Instruction::Trap(_) => with_value(Instruction::Load(7, 1), value),
+                        Instruction::Store(sr, _)
+                        | Instruction::StoreRelative(sr, _, _)
+                        | Instruction::StoreIndirect(sr, _) => {
+                            with_value(Instruction::StoreIndirect(sr, 1), value)
+                        }
+                        _ => Vec::new(),
+                    },
+                ),
+            )
+            .flat_map(|word| vec![(word >> 8) as u8, word as u8])
+            .collect()
+    }
+
+    /// Every address holding `needle`, skipping the device registers unless `include_devices`
+    /// is set.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(0x3000, ".FILL xBEEF").unwrap();
+    /// simulator.write(0x4567, 0xBEEF);
+    ///
+    /// assert_eq!(simulator.find(0xBEEF, false), vec![0x3000, 0x4567]);
+    ///
+    /// // The clock is running, so the MCR holds x8000
+    /// assert!(simulator.find(0x8000, false).is_empty());
+    /// assert!(simulator.find(0x8000, true).contains(&0xFFFE));
+    /// ```
+    #[must_use]
+    pub fn find(&self, needle: u16, include_devices: bool) -> Vec<u16> {
+        self.find_pattern(&[needle], include_devices)
+    }
+
+    /// The starting address of every occurrence of the consecutive words in `pattern`, skipping
+    /// the device registers unless `include_devices` is set.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(0x3000, ".STRINGZ \"Hi\"").unwrap();
+    /// simulator.write(0x3100, 0x0048);
+    ///
+    /// assert_eq!(simulator.find_pattern(&[0x0048, 0x0069], false), vec![0x3000]);
+    /// ```
+    #[must_use]
+    pub fn find_pattern(&self, pattern: &[u16], include_devices: bool) -> Vec<u16> {
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+
+        let searched = if include_devices {
+            &self.memory[..]
+        } else {
+            &self.memory[..DEVICE_REGISTERS]
+        };
+
+        searched
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, words)| *words == pattern)
+            .map(|(address, _)| address as u16)
+            .collect()
+    }
+
+    /// The display the simulator is writing output to.
+    #[must_use]
+    pub fn display(&self) -> &Writer {
+        &self.display
+    }
+
+    /// Remember which instruction wrote each character sent to the display, for
+    /// [`output_provenance`](#method.output_provenance).
+    #[must_use]
+    pub fn with_output_provenance(mut self, record: bool) -> Self {
+        self.provenance = if record { Some(Vec::new()) } else { None };
+        self
+    }
+
+    /// Each character written to the display, along with the address of the instruction
+    /// which wrote it, or nothing if they weren't being recorded.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R0, LETTER
+    ///              STI R0, DDR
+    ///              AND R0, R0, #0
+    ///              STI R0, MCR
+    ///      LETTER  .FILL x41
+    ///      DDR     .FILL xFE06
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_output_provenance(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.output_provenance(), [(0x3001, b'A')]);
+    /// ```
+    #[must_use]
+    pub fn output_provenance(&self) -> Vec<(u16, u8)> {
+        self.provenance.clone().unwrap_or_default()
+    }
+
+    /// Count how many times each address is accessed, whether an instruction is fetched from
+    /// it or data is read from or stored to it. Device registers aren't counted.
+    #[must_use]
+    pub fn with_access_counting(mut self, count: bool) -> Self {
+        self.access_counts = if count {
+            Some(vec![0; DEVICE_REGISTERS])
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Every address which has been accessed, along with how many times, in order of address
+    #[must_use]
+    pub fn access_counts(&self) -> Vec<(u16, u64)> {
+        self.access_counts
+            .iter()
+            .flat_map(|counts| counts.iter().enumerate())
+            .filter(|&(_, &count)| count != 0)
+            .map(|(address, &count)| (address as u16, count))
+            .collect()
+    }
+
+    /// The access counts drawn as a grid of 16 addresses per row, where denser characters mean
+    /// more accesses relative to the busiest address. Rows nothing was accessed in are left
+    /// out.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        AND R1, R1, #0
+    ///              ADD R1, R1, #5
+    ///      LOOP    LD R0, DATA
+    ///              ADD R1, R1, #-1
+    ///              BRp LOOP
+    ///              STI R1, MCR
+    ///      DATA    .FILL #7
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap()
+    /// .with_access_counting(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.access_counts()[..3], [(0x3000, 1), (0x3001, 1), (0x3002, 5)]);
+    /// assert_eq!(simulator.access_heatmap(), "x3000 |::@@@:@:        |\n");
+    /// ```
+    #[must_use]
+    pub fn access_heatmap(&self) -> String {
+        const SHADES: &[u8] = b" .:-=+*#%@";
+
+        let counts = match self.access_counts {
+            Some(ref counts) => counts,
+            None => return String::new(),
+        };
+        let busiest = counts.iter().copied().max().unwrap_or(0);
+        let shades = SHADES.len() as u64 - 1;
+
+        counts
+            .chunks(16)
+            .enumerate()
+            .filter(|(_, row)| row.iter().any(|&count| count != 0))
+            .map(|(i, row)| {
+                let cells = row
+                    .iter()
+                    .map(|&count| {
+                        // Anything accessed at all gets at least the lightest shade
+                        SHADES[((count * shades).div_ceil(busiest.max(1))) as usize] as char
+                    })
+                    .collect::<String>();
+                format!(
+                    "{} |{}|\n",
+                    self.formatter.assembly(i as u16 * 16, 4),
+                    cells
+                )
+            })
+            .collect()
+    }
+
+    /// Hold back everything the program prints until it stops, and only send it to the
+    /// display if the program halted cleanly, so that a program which crashes part way
+    /// through prints nothing at all.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let run = |last: &str| {
+    ///     let mut simulator = Simulator::from_source(
+    ///         0x3000,
+    ///         &format!(
+    ///             "        LD R0, LETTER
+    ///                      STI R0, DDR
+    ///                      AND R0, R0, #0
+    ///                      {}
+    ///              LETTER  .FILL x41
+    ///              DDR     .FILL xFE06
+    ///              MCR     .FILL xFFFE",
+    ///             last
+    ///         ),
+    ///     )
+    ///     .unwrap()
+    ///     .with_output_on_clean_halt(true);
+    ///
+    ///     simulator.execute();
+    ///     simulator.display().contents().to_vec()
+    /// };
+    ///
+    /// assert_eq!(run("STI R0, MCR"), b"A");
+    /// assert_eq!(run(".FILL xD000"), b"");
+    /// ```
+    #[must_use]
+    pub fn with_output_on_clean_halt(mut self, hold: bool) -> Self {
+        self.held_output = if hold { Some(Vec::new()) } else { None };
+        self
+    }
+
+    /// Choose what happens when a program is too large to fit between its origin and the end of memory.
+    ///
+    /// By default such programs are refused.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{OverflowPolicy, Reader, Simulator, Tracer, Writer};
+    /// use std::io::ErrorKind;
+    ///
+    /// // Three words starting at 0xFFFE, so the last one won't fit
+    /// let program = ".FILL #1\n.FILL #2\n.FILL #3";
+    ///
+    /// let error = Simulator::from_source(0xFFFE, program).err().unwrap();
+    /// assert_eq!(error.kind(), ErrorKind::InvalidData);
+    ///
+    /// let simulator = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .with_overflow_policy(OverflowPolicy::Wrap)
+    ///     .load_source(0xFFFE, program)
+    ///     .unwrap();
+    /// assert_eq!(simulator.memory()[0xFFFF], 0x0002);
+    /// assert_eq!(simulator.memory()[0x0000], 0x0003);
+    /// ```
+    #[must_use]
+    pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Choose how traced instructions are written to the trace.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, TraceFormat, Tracer, Writer};
+    ///
+    /// let trace = std::env::temp_dir()
+    ///     .join(format!("lc3sim_pretty_{}.trace", std::process::id()));
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::from((trace.to_str(), None, false)),
+    /// )
+    /// .with_trace_format(TraceFormat::Pretty)
+    /// .load_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #1
+    ///              ADD R2, R1, #2
+    ///              STI R3, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    /// // Dropping the simulator flushes the trace
+    /// drop(simulator);
+    ///
+    /// assert_eq!(
+    ///     std::fs::read_to_string(trace).unwrap(),
+    ///     "x3000  1261  ADD R1, R1, #1          R1: x0000 -> x0001\n\
+    ///      x3001  1462  ADD R2, R1, #2          R2: x0000 -> x0003\n\
+    ///      x3002  B600  STI R3, MCR ; 0x3003\n"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_trace_format(mut self, format: TraceFormat) -> Self {
+        self.trace_format = format;
+        self
+    }
+
+    /// Only trace instructions while the word at `address` is nonzero, so that a program can
+    /// turn tracing on and off around the part of it that's interesting. The gate is checked
+    /// after each instruction executes.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, TraceFormat, Tracer, Writer};
+    ///
+    /// let trace = std::env::temp_dir().join(format!("lc3sim_gated_{}.trace", std::process::id()));
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::from((trace.to_str(), None, false)),
+    /// )
+    /// .with_trace_format(TraceFormat::Pretty)
+    /// .with_trace_gate(0x3007)
+    /// // Increment R1 three times, opening the gate before the second and closing it after
+    /// .load_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #1
+    ///              ST R1, GATE
+    ///              ADD R1, R1, #1
+    ///              ST R2, GATE
+    ///              ADD R1, R1, #1
+    ///              STI R2, MCR
+    ///      MCR     .FILL xFFFE
+    ///      GATE    .FILL #0",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    /// drop(simulator);
+    ///
+    /// assert_eq!(
+    ///     std::fs::read_to_string(trace).unwrap(),
+    ///     "x3001  3205  ST R1, GATE ; 0x3007\n\
+    ///      x3002  1261  ADD R1, R1, #1          R1: x0001 -> x0002\n"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_trace_gate(mut self, address: u16) -> Self {
+        self.trace_gate = Some(address);
+        self
+    }
+
+    /// Add a line to the trace for every read and store an instruction makes between `start`
+    /// and `end` (inclusive), before the instruction itself is traced. Keeping the range to
+    /// the program's own data stops the trace being swamped by polling the device registers.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, TraceFormat, Tracer, Writer};
+    ///
+    /// let trace = std::env::temp_dir()
+    ///     .join(format!("lc3sim_memory_{}.trace", std::process::id()));
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::from((trace.to_str(), None, false)),
+    /// )
+    /// .with_trace_format(TraceFormat::Pretty)
+    /// .with_memory_trace(0x3000, 0x30FF)
+    /// .load_source(
+    ///     0x3000,
+    ///     "        LD R0, COUNT
+    ///              ADD R0, R0, #1
+    ///              ST R0, COUNT
+    ///              AND R1, R1, #0
+    ///              STI R1, MCR
+    ///      COUNT   .FILL x29
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    /// drop(simulator);
+    ///
+    /// let trace = std::fs::read_to_string(trace).unwrap();
+    /// let lines = trace.lines().collect::<Vec<_>>();
+    ///
+    /// assert_eq!(lines[0], "x3000  read   x3005  x0029");
+    /// assert!(lines[1].starts_with("x3000  2004  LD R0, COUNT ; 0x3005"));
+    /// assert_eq!(lines[3], "x3002  store  x3005  x0029 -> x002A");
+    /// // The MCR is read as a pointer, but the store through it is outside the range
+    /// assert_eq!(lines[6], "x3004  read   x3006  xFFFE");
+    /// assert_eq!(lines.len(), 8);
+    /// ```
+    #[must_use]
+    pub fn with_memory_trace(mut self, start: u16, end: u16) -> Self {
+        self.memory_trace = Some((start, end));
+        self
+    }
+
+    /// After each conditional branch, add a line to the trace showing what the branch
+    /// predictor guessed, what the branch actually did, and how the predictor changed.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, TraceFormat, Tracer, Writer};
+    ///
+    /// let trace = std::env::temp_dir()
+    ///     .join(format!("lc3sim_prediction_{}.trace", std::process::id()));
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::from((trace.to_str(), Some(vec!["BR"]), false)),
+    /// )
+    /// .with_trace_format(TraceFormat::Pretty)
+    /// .with_prediction_trace(true)
+    /// // Count R1 down from 3, then stop the clock
+    /// .load_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #3
+    ///      LOOP    ADD R1, R1, #-1
+    ///              BRp LOOP
+    ///              STI R2, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    /// drop(simulator);
+    ///
+    /// assert_eq!(
+    ///     std::fs::read_to_string(trace)
+    ///         .unwrap()
+    ///         .lines()
+    ///         .filter(|line| line.contains("predicted"))
+    ///         .collect::<Vec<_>>(),
+    ///     [
+    ///         "x3002  BRp #-2  predicted not taken, was taken (mispredicted)  weakly not taken -> weakly taken",
+    ///         "x3002  BRp #-2  predicted taken, was taken (correct)  weakly taken -> strongly taken",
+    ///         "x3002  BRp #-2  predicted taken, was not taken (mispredicted)  strongly taken -> weakly taken",
+    ///     ]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_prediction_trace(mut self, trace: bool) -> Self {
+        self.trace_prediction = trace;
+        self
+    }
+
+    /// Choose which branch predictor to use, which by default is a two bit counter for each
+    /// branch.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{PredictorKind, Simulator};
+    ///
+    /// let accuracy = |kind| {
+    ///     let mut simulator = Simulator::from_source(
+    ///         0x3000,
+    ///         "        ADD R1, R1, #15
+    ///          LOOP    AND R2, R1, #1
+    ///                  BRz EVEN        ; Taken every other time around the loop
+    ///                  ADD R3, R3, #1
+    ///          EVEN    ADD R1, R1, #-1
+    ///                  BRp LOOP
+    ///                  STI R2, MCR
+    ///          MCR     .FILL xFFFE",
+    ///     )
+    ///     .unwrap()
+    ///     .with_predictor_kind(kind);
+    ///
+    ///     simulator.execute();
+    ///     simulator.prediction_stats().accuracy()
+    /// };
+    ///
+    /// let never_taken = accuracy(PredictorKind::Static);
+    /// let bimodal = accuracy(PredictorKind::Bimodal);
+    /// let gshare = accuracy(PredictorKind::GShare { history_bits: 4 });
+    ///
+    /// // Only a predictor which knows what the last branch did can follow the alternation
+    /// assert!(never_taken < bimodal);
+    /// assert!(bimodal < gshare);
+    /// ```
+    ///
+    /// # Panics
+    /// Will panic if a gshare predictor has more than 16 bits of history
+    #[must_use]
+    pub fn with_predictor_kind(mut self, kind: PredictorKind) -> Self {
+        self.shared_predictors = match kind {
+            PredictorKind::GShare { history_bits } => {
+                assert!(
+                    history_bits <= 16,
+                    "A gshare predictor can't keep {} bits of history",
+                    history_bits
+                );
+                vec![Predictor::default(); 1 << history_bits]
+            }
+            _ => Vec::new(),
+        };
+        self.predictor_kind = kind;
+        self
+    }
+
+    /// The state of the predictor for the conditional branch at `address`. Each branch has
+    /// its own predictor, which starts out weakly not taken. This is only kept for the
+    /// default, bimodal, predictor.
+    #[must_use]
+    pub fn predictor(&self, address: u16) -> Predictor {
+        self.predictors
+            .get(&address)
+            .map(|&(predictor, _, _)| predictor)
+            .unwrap_or_default()
+    }
+
+    /// How many conditional branches have been executed, and how many of those were
+    /// predicted correctly, across every branch.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #4
+    ///      LOOP    ADD R1, R1, #-1
+    ///              BRp LOOP
+    ///              STI R1, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// // Wrong the first time, before the predictor has warmed up, and when the loop exits
+    /// let stats = simulator.prediction_stats();
+    /// assert_eq!((stats.total, stats.correct), (4, 2));
+    /// assert_eq!(stats.accuracy(), 0.5);
+    /// ```
+    #[must_use]
+    pub fn prediction_stats(&self) -> PredictionStats {
+        self.predictors.values().fold(
+            PredictionStats::default(),
+            |stats, &(_, total, mispredicted)| PredictionStats {
+                total: stats.total + total,
+                correct: stats.correct + total - mispredicted,
+            },
+        )
+    }
+
+    /// How well each conditional branch executed has been predicted by its own predictor,
+    /// in address order, for finding the branches which are predicted poorly.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{PredictionStats, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #4
+    ///      LOOP    ADD R1, R1, #-1
+    ///              BRz DONE        ; Never taken until the loop exits
+    ///              BRnzp LOOP      ; Unconditional, so never predicted
+    ///      DONE    STI R1, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(
+    ///     simulator.prediction_stats_by_address(),
+    ///     [(0x3002, PredictionStats { total: 4, correct: 3 })]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn prediction_stats_by_address(&self) -> Vec<(u16, PredictionStats)> {
+        let mut stats = self
+            .predictors
+            .iter()
+            .map(|(&address, &(_, total, mispredicted))| {
+                (
+                    address,
+                    PredictionStats {
+                        total,
+                        correct: total - mispredicted,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        stats.sort_unstable_by_key(|&(address, _)| address);
+        stats
+    }
+
+    /// The address of every conditional branch executed, with how many times it was
+    /// executed and how many of those its predictor got wrong, most mispredicted first.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// // Three times, count R1 down from 3, then stop the clock
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R2, R2, #3
+    ///      OUTER   ADD R1, R1, #3
+    ///      INNER   ADD R1, R1, #-1
+    ///              BRp INNER
+    ///              ADD R2, R2, #-1
+    ///              BRp OUTER
+    ///              STI R3, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// // The inner loop's branch mispredicts while warming up, then once each time it exits
+    /// assert_eq!(
+    ///     simulator.misprediction_report(),
+    ///     [(0x3003, 9, 4), (0x3005, 3, 2)]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn misprediction_report(&self) -> Vec<(u16, u64, u64)> {
+        let mut report = self
+            .predictors
+            .iter()
+            .map(|(&address, &(_, total, mispredicted))| (address, total, mispredicted))
+            .collect::<Vec<_>>();
+        report.sort_unstable_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        report
+    }
+
+    /// Choose how hex numbers are written in traces, disassembly, the state report, and
+    /// warnings.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Formatter, Reader, Simulator, TraceFormat, Tracer, Writer};
+    ///
+    /// let trace = std::env::temp_dir()
+    ///     .join(format!("lc3sim_formatted_{}.trace", std::process::id()));
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::from((trace.to_str(), None, false)),
+    /// )
+    /// .with_trace_format(TraceFormat::Pretty)
+    /// .with_formatter(Formatter::default().with_prefix("0x").with_lowercase(true))
+    /// .load_source(
+    ///     0x3000,
+    ///     "        NOT R1, R1
+    ///              .FILL xDEAD     ; The reserved opcode",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.step();
+    ///
+    /// assert_eq!(simulator.disassemble(0x3001), ".FILL 0xdead");
+    /// assert!(simulator.state_report().contains("R1: 0xffff (-1)"));
+    ///
+    /// drop(simulator);
+    /// assert_eq!(
+    ///     std::fs::read_to_string(trace).unwrap(),
+    ///     "0x3000  927f  NOT R1, R1              R1: 0x0000 -> 0xffff\n"
+    /// );
+    ///
+    /// // ADD R0, R1, R7 with bit 3 set
+    /// let simulator = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .with_formatter(Formatter::default().with_prefix("0x").with_lowercase(true))
+    ///     .with_encoding_check(true)
+    ///     .load_source(0x3ABC, ".FILL x104F")
+    ///     .unwrap();
+    ///
+    /// assert_eq!(
+    ///     simulator.warnings(),
+    ///     ["Word 0x104f at 0x3abc has unused bits set (expected 0x1047 for ADD R0, R1, R7)"]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_formatter(mut self, formatter: Formatter) -> Self {
+        self.formatter = formatter;
+        self
+    }
+
+    /// How hex numbers are being written
+    #[must_use]
+    pub fn formatter(&self) -> &Formatter {
+        &self.formatter
+    }
+
+    /// Keep the traces of the last `size` instructions in memory, whether or not they're
+    /// wanted by the tracer, so that they can be looked at with
+    /// [`crash_trace`](#method.crash_trace) if the machine stops because of an error.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Simulator, TraceFormat};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R0, R0, #1
+    ///              ADD R0, R0, #2
+    ///              ADD R0, R0, #3
+    ///              ADD R0, R0, #4
+    ///              .FILL xD000     ; The reserved opcode, which there's no handler for",
+    /// )
+    /// .unwrap()
+    /// .with_trace_format(TraceFormat::Pretty)
+    /// .with_trace_ring(2);
+    ///
+    /// simulator.execute();
+    ///
+    /// // Only the last two instructions, including the one which faulted, are kept
+    /// assert_eq!(
+    ///     simulator.crash_trace(),
+    ///     vec![
+    ///         "x3003  1024  ADD R0, R0, #4          R0: x0006 -> x000A",
+    ///         "x3004  D000  .FILL xD000",
+    ///     ]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_trace_ring(mut self, size: usize) -> Self {
+        self.ring_size = size;
+        self.ring = VecDeque::with_capacity(size);
+        self
+    }
+
+    /// The traces kept by [`with_trace_ring`](#method.with_trace_ring), oldest first, if the
+    /// machine stopped because of an error. Empty if it's still running or halted normally.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, TraceFormat, Tracer, Writer};
+    /// use std::io::{Error, ErrorKind, Write};
+    ///
+    /// // A display which has gone away
+    /// struct Broken;
+    ///
+    /// impl Write for Broken {
+    ///     fn write(&mut self, _: &[u8]) -> Result<usize, Error> {
+    ///         Err(Error::new(ErrorKind::BrokenPipe, "gone"))
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> Result<(), Error> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Custom(Box::new(Broken)),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .with_trace_format(TraceFormat::Pretty)
+    /// .with_trace_ring(3)
+    /// // Wait for the display, then write to it, forever
+    /// .load_source(
+    ///     0x3000,
+    ///     "WAIT    LDI R0, DSR
+    ///              BRzp WAIT
+    ///              STI R1, DDR
+    ///              BRnzp WAIT
+    ///      DSR     .FILL xFE04
+    ///      DDR     .FILL xFE06",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(
+    ///     simulator.crash_trace(),
+    ///     vec![
+    ///         "x3000  A003  LDI R0, DSR ; 0x3004",
+    ///         "x3001  07FE  BRzp WAIT ; 0x3000",
+    ///         "x3000  A003  LDI R0, DSR ; 0x3004",
+    ///     ]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn crash_trace(&self) -> Vec<&str> {
+        if self.faulted {
+            self.ring.iter().map(|trace| trace.trim_end()).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// The entire contents of memory, as last written (device registers aren't polled). This
+    /// covers every address, all the way up to and including xFFFF.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R1, TOP
+    ///              LD R2, VALUE
+    ///              STR R2, R1, #0
+    ///              LDR R3, R1, #0
+    ///              AND R0, R0, #0
+    ///              STI R0, MCR
+    ///      TOP     .FILL xFFFF
+    ///      VALUE   .FILL x1234
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.memory().len(), 0x10000);
+    /// assert_eq!(simulator.memory()[0xFFFF], 0x1234);
+    /// assert_eq!(simulator.registers()[3], 0x1234);
+    /// ```
+    #[must_use]
+    pub fn memory(&self) -> &[u16] {
+        &self.memory
+    }
+
+    /// Annotate regions of memory as code, data, or stack using a JSON metadata file (see
+    /// [`Metadata`](metadata/struct.Metadata.html)). Data is disassembled as `.FILL`, and
+    /// stores into code produce a warning.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// // x3001 is data
+    /// let metadata = std::env::temp_dir()
+    ///     .join(format!("lc3sim_metadata_{}.json", std::process::id()));
+    /// std::fs::write(
+    ///     &metadata,
+    ///     r#"{ "regions": [{ "start": 12289, "end": 12289, "kind": "data" }] }"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #1
+    ///              ADD R1, R1, #1",
+    /// )
+    /// .unwrap()
+    /// .with_metadata(metadata.to_str().unwrap())
+    /// .unwrap();
+    ///
+    /// assert_eq!(simulator.disassemble(0x3000), "ADD R1, R1, #1");
+    /// assert_eq!(simulator.disassemble(0x3001), ".FILL x1261");
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the file couldn't be read, or isn't valid metadata
+    pub fn with_metadata(mut self, file: &str) -> Result<Self, Error> {
+        self.metadata = Metadata::from_file(file)?;
+        Ok(self)
+    }
+
+    /// Read the labels from a symbol table, such as the `.sym` file lc3as writes alongside
+    /// the object file, so that the trace can show them. Each line holding a label and then
+    /// its address in hex is used, whether or not it's commented out with `//` as lc3as
+    /// writes it, and anything else is skipped.
+    ///
+    /// The Pretty trace shows the label a PC relative instruction refers to, and the Full
+    /// trace shows the PC relative to the closest label before it.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, TraceFormat, Tracer, Writer};
+    ///
+    /// let symbols = std::env::temp_dir()
+    ///     .join(format!("lc3sim_program_{}.sym", std::process::id()));
+    /// std::fs::write(
+    ///     &symbols,
+    ///     "// Symbol table\n\
+    ///      // Scope level 0:\n\
+    ///      //\tSymbol Name       Page Address\n\
+    ///      //\t----------------  ------------\n\
+    ///      //\tMAIN              3000\n\
+    ///      //\tSUB               3003\n\
+    ///      //\tMCR               3005\n",
+    /// )
+    /// .unwrap();
+    ///
+    /// let run = |format: TraceFormat| {
+    ///     let trace = std::env::temp_dir()
+    ///         .join(format!("lc3sim_labelled_{}.trace", std::process::id()));
+    ///     let mut simulator = Simulator::new(
+    ///         Reader::from(""),
+    ///         Writer::Buffer(Vec::new()),
+    ///         Tracer::from((trace.to_str(), None, false)),
+    ///     )
+    ///     .with_trace_format(format)
+    ///     .load_bytes(&[
+    ///         0x30, 0x00, 0x48, 0x02, 0x50, 0x20, 0xB0, 0x02, 0x12, 0x61, 0xC1, 0xC0, 0xFF, 0xFE,
+    ///     ])
+    ///     .unwrap()
+    ///     .load_symbols(symbols.to_str().unwrap())
+    ///     .unwrap();
+    ///
+    ///     simulator.execute();
+    ///     drop(simulator);
+    ///     std::fs::read_to_string(trace).unwrap()
+    /// };
+    ///
+    /// let pretty = run(TraceFormat::Pretty);
+    /// assert!(pretty.starts_with("x3000  4802  JSR SUB ; 0x3003"));
+    /// assert!(pretty.contains("x3002  B002  STI R0, MCR ; 0x3005"));
+    ///
+    /// let full = run(TraceFormat::Full);
+    /// assert!(full.contains("Program Counter: 0x3003 (SUB)\n"));
+    /// assert!(full.contains("Program Counter: 0x3004 (SUB+1)\n"));
+    /// assert!(full.contains("Program Counter: 0x3001 (MAIN+1)\n"));
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the file couldn't be read
+    pub fn load_symbols(mut self, file: &str) -> Result<Self, Error> {
+        for line in fs::read_to_string(file)?.lines() {
+            let line = line.trim_start();
+            let line = line.strip_prefix("//").unwrap_or(line);
+
+            if let [label, address] = line.split_whitespace().collect::<Vec<_>>()[..] {
+                let digits = address
+                    .trim_start_matches("0x")
+                    .trim_start_matches(['x', 'X']);
+                if let Ok(address) = u16::from_str_radix(digits, 16) {
+                    self.add_symbol(label, address);
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Name `address` with `label`. Where several labels share an address, the same one is
+    /// kept every time.
+    fn add_symbol(&mut self, label: &str, address: u16) {
+        let symbol = self
+            .symbols
+            .entry(address)
+            .or_insert_with(|| String::from(label));
+        if label < symbol.as_str() {
+            *symbol = String::from(label);
+        }
+    }
+
+    /// `address` as an offset from the closest label at or before it, if there's one close by
+    fn label_of(&self, address: u16) -> Option<String> {
+        // The symbols are kept in address order, so the closest label is the last one up to
+        // `address`
+        self.symbols
+            .range(..=address)
+            .next_back()
+            .filter(|(&labelled, _)| address - labelled <= NEARBY_LABEL)
+            .map(|(&labelled, label)| match address - labelled {
+                0 => label.clone(),
+                offset => format!("{}+{}", label, offset),
+            })
+    }
+
+    #[must_use]
+    pub fn with_operating_system(self, file: &str) -> Self {
+        self.load(file).expect("Unable to load Operating System")
+    }
+
+    /// Load the specified file into the simulator. Files ending in `.gz` are decompressed
+    /// first, when built with the `flate2` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "flate2")]
+    /// # {
+    /// use flate2::{write::GzEncoder, Compression};
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::Write;
+    ///
+    /// let program = [0x30, 0x00, 0x12, 0x61, 0xF0, 0x25];
+    ///
+    /// let plain = std::env::temp_dir().join(format!("lc3sim_plain_{}.obj", std::process::id()));
+    /// std::fs::write(&plain, program).unwrap();
+    ///
+    /// let compressed = std::env::temp_dir()
+    ///     .join(format!("lc3sim_compressed_{}.obj.gz", std::process::id()));
+    /// let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    /// encoder.write_all(&program).unwrap();
+    /// std::fs::write(&compressed, encoder.finish().unwrap()).unwrap();
+    ///
+    /// let simulator = || Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace);
+    /// let plain = simulator().load(plain.to_str().unwrap()).unwrap();
+    /// let compressed = simulator().load(compressed.to_str().unwrap()).unwrap();
+    ///
+    /// assert_eq!(&compressed.memory()[0x3000..0x3002], [0x1261, 0xF025]);
+    /// assert_eq!(compressed.memory(), plain.memory());
+    /// # }
+    /// ```
+    ///
+    /// A byte order mark left at the start of the file by an editor is skipped.
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let file = std::env::temp_dir().join(format!("lc3sim_bom_{}.obj", std::process::id()));
+    /// std::fs::write(&file, [0xEF, 0xBB, 0xBF, 0x30, 0x00, 0x12, 0x61]).unwrap();
+    ///
+    /// let simulator = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .load(file.to_str().unwrap())
+    ///     .unwrap();
+    ///
+    /// assert_eq!(simulator.entry_point(), 0x3000);
+    /// assert_eq!(simulator.memory()[0x3000], 0x1261);
+    /// ```
+    ///
+    /// Anything other than a regular file is refused.
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::ErrorKind;
+    ///
+    /// let directory = std::env::temp_dir();
+    /// let error = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .load(directory.to_str().unwrap())
+    ///     .err()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    /// assert!(error.to_string().ends_with("as it isn't a regular file"));
+    /// ```
+    ///
+    /// So is a file too short to hold an origin, or which ends part way through a word.
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::ErrorKind;
+    ///
+    /// let load = |bytes: &[u8]| {
+    ///     let file = std::env::temp_dir()
+    ///         .join(format!("lc3sim_truncated_{}.obj", std::process::id()));
+    ///     std::fs::write(&file, bytes).unwrap();
+    ///
+    ///     Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///         .load(file.to_str().unwrap())
+    ///         .err()
+    ///         .unwrap()
+    /// };
+    ///
+    /// let error = load(&[0x30]);
+    /// assert_eq!(error.kind(), ErrorKind::InvalidData);
+    /// assert!(error.to_string().ends_with("but there are only 1"));
+    ///
+    /// let error = load(&[0x30, 0x00, 0x12]);
+    /// assert_eq!(error.kind(), ErrorKind::InvalidData);
+    /// assert!(error.to_string().ends_with("but there are 3 bytes"));
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the supplied file isn't a regular file or was unable to be read
+    /// from, if it isn't made of an origin and whole words, or if it doesn't fit in memory
+    /// and the overflow policy is `OverflowPolicy::Error`
+    pub fn load(self, file: &str) -> Result<Self, Error> {
+        self.load_bytes(&read_object(file)?)
+    }
+
+    /// Load a program from the bytes of an object file, without needing the file itself, such
+    /// as an operating system built in with `include_bytes!`.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .load_bytes(&[0x30, 0x00, 0x12, 0x61, 0xF0, 0x25])
+    /// .unwrap();
+    ///
+    /// assert_eq!(simulator.entry_point(), 0x3000);
+    /// assert_eq!(simulator.memory()[0x3000..0x3002], [0x1261, 0xF025]);
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if there are fewer than two bytes or an odd number of them, or if the
+    /// program doesn't fit in memory and the overflow policy is `OverflowPolicy::Error`
+    pub fn load_bytes(mut self, bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 2 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "An object file needs 2 bytes for its origin, but there are only {}",
+                    bytes.len()
+                ),
+            ));
+        }
+
+        if !bytes.len().is_multiple_of(2) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "An object file is made of whole words, but there are {} bytes",
+                    bytes.len()
+                ),
+            ));
+        }
+
+        let address = u16::from(bytes[0]) << 8 | u16::from(bytes[1]);
+        let words = bytes[2..]
+            .chunks_exact(2)
+            .map(|bytes| u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+            .collect::<Vec<_>>();
+
+        self.load_words(address, &words)?;
+        Ok(self)
+    }
+
+    /// Load a bundle of several programs from one file, where each segment is an origin, the
+    /// number of words in the segment, and then those words. Every segment is loaded at its
+    /// own origin, and execution starts at the origin of the first.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let bundle = std::env::temp_dir().join(format!("lc3sim_bundle_{}.obj", std::process::id()));
+    /// std::fs::write(
+    ///     &bundle,
+    ///     [
+    ///         0x30, 0x00, 0x00, 0x02, 0x12, 0x61, 0xF0, 0x25, // Two words of code at x3000
+    ///         0x40, 0x00, 0x00, 0x01, 0x00, 0x2A, // One word of data at x4000
+    ///     ],
+    /// )
+    /// .unwrap();
+    ///
+    /// let simulator = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .load_multi(bundle.to_str().unwrap())
+    ///     .unwrap();
+    ///
+    /// assert_eq!(simulator.pc(), 0x3000);
+    /// assert_eq!(simulator.memory()[0x3000..0x3002], [0x1261, 0xF025]);
+    /// assert_eq!(simulator.memory()[0x4000], 0x002A);
+    /// ```
+    ///
+    /// A segment which claims more words than the file has left is refused.
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::ErrorKind;
+    ///
+    /// let bundle = std::env::temp_dir()
+    ///     .join(format!("lc3sim_short_bundle_{}.obj", std::process::id()));
+    /// std::fs::write(&bundle, [0x30, 0x00, 0x00, 0x03, 0x12, 0x61, 0xF0, 0x25]).unwrap();
+    ///
+    /// let error = Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .load_multi(bundle.to_str().unwrap())
+    ///     .err()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(error.kind(), ErrorKind::InvalidData);
+    /// assert!(error.to_string().ends_with("claims 3 words, but only 4 bytes remain"));
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the file couldn't be read, if a segment claims more words than are
+    /// left in the file, or if a segment doesn't fit in memory and the overflow policy is
+    /// `OverflowPolicy::Error`
+    pub fn load_multi(mut self, file: &str) -> Result<Self, Error> {
+        let buffer = read_object(file)?;
+        let mut entry_point = None;
+        let mut offset = 0;
+
+        while offset < buffer.len() {
+            let word = |at: usize| u16::from(buffer[at]) << 8 | u16::from(buffer[at + 1]);
+
+            if buffer.len() - offset < 4 {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Segment at byte {} of {} is too short to hold an origin and length",
+                        offset, file
+                    ),
+                ));
+            }
+
+            let origin = word(offset);
+            let length = usize::from(word(offset + 2));
+            let payload = offset + 4;
+
+            if buffer.len() - payload < length * 2 {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Segment at byte {} of {} claims {} words, but only {} bytes remain",
+                        offset,
+                        file,
+                        length,
+                        buffer.len() - payload
+                    ),
+                ));
+            }
+
+            let words = buffer[payload..payload + length * 2]
+                .chunks_exact(2)
+                .map(|bytes| u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+                .collect::<Vec<_>>();
+
+            self.load_words(origin, &words)?;
+            entry_point.get_or_insert(origin);
+            offset = payload + length * 2;
+        }
+
+        if let Some(entry_point) = entry_point {
+            self.pc = entry_point;
+            self.entry_point = entry_point;
+        }
+
+        Ok(self)
+    }
+
+    /// Load an Intel HEX file, as made by many other assemblers. Bytes are paired up into big
+    /// endian words, so byte address 0x6000 is the high byte of the word at x3000, and
+    /// execution starts at the lowest address loaded. A byte set without the other half of
+    /// its word leaves that half as it was.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let file = std::env::temp_dir().join(format!("lc3sim_program_{}.hex", std::process::id()));
+    /// std::fs::write(&file, ":046000001261F02514\n:00000001FF\n").unwrap();
+    ///
+    /// let simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .load_hex(file.to_str().unwrap())
+    /// .unwrap();
+    ///
+    /// assert_eq!(simulator.pc(), 0x3000);
+    /// assert_eq!(simulator.memory()[0x3000..0x3002], [0x1261, 0xF025]);
+    /// ```
+    ///
+    /// A record whose checksum doesn't match is refused, rather than loading whatever it
+    /// holds.
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::ErrorKind;
+    ///
+    /// let file = std::env::temp_dir().join(format!("lc3sim_corrupt_{}.hex", std::process::id()));
+    /// std::fs::write(&file, ":046000001261F02515\n:00000001FF\n").unwrap();
+    ///
+    /// let error = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .load_hex(file.to_str().unwrap())
+    /// .err()
+    /// .unwrap();
+    ///
+    /// assert_eq!(error.kind(), ErrorKind::InvalidData);
+    /// assert!(error.to_string().starts_with("Line 1 of"));
+    /// assert!(error.to_string().ends_with("has checksum 0x15, but its contents need 0x14"));
+    ///
+    /// // Data can't run off the end of memory, however far out its base address is
+    /// std::fs::write(&file, ":02000004FFFFFC\n:02FFFF00000000\n:00000001FF\n").unwrap();
+    ///
+    /// let error = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .load_hex(file.to_str().unwrap())
+    /// .err()
+    /// .unwrap();
+    ///
+    /// assert_eq!(error.kind(), ErrorKind::InvalidData);
+    /// assert!(error.to_string().ends_with("sets byte 0xFFFFFFFF, which is past the end of memory"));
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the file couldn't be read, if any record is malformed or has the
+    /// wrong checksum, or if it sets memory past xFFFF
+    pub fn load_hex(mut self, file: &str) -> Result<Self, Error> {
+        let text = String::from_utf8(read_object(file)?).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("{} isn't text, so it can't be an Intel HEX file", file),
+            )
+        })?;
+
+        let mut words: BTreeMap<u16, u16> = BTreeMap::new();
+        for (address, byte) in intel_hex::parse(&text, file)? {
+            let word_address = (address >> 1) as u16;
+            let word = words
+                .entry(word_address)
+                .or_insert(self.memory[word_address as usize]);
+            *word = if address & 1 == 0 {
+                *word & 0x00FF | u16::from(byte) << 8
+            } else {
+                *word & 0xFF00 | u16::from(byte)
+            };
+        }
+
+        // Each run of consecutive words is loaded as a program of its own
+        let mut runs: Vec<(u16, Vec<u16>)> = Vec::new();
+        for (address, word) in words {
+            match runs.last_mut() {
+                Some((origin, run)) if usize::from(*origin) + run.len() == usize::from(address) => {
+                    run.push(word)
+                }
+                _ => runs.push((address, vec![word])),
+            }
+        }
+
+        for (origin, run) in &runs {
+            self.load_words(*origin, run)?;
+        }
+        if let Some(&(entry_point, _)) = runs.first() {
+            self.pc = entry_point;
+            self.entry_point = entry_point;
+        }
+
+        Ok(self)
+    }
+
+    /// Assemble `source` and load it into the simulator, starting at `origin` unless the
+    /// source has an `.ORIG` of its own. See [`assemble`](assembler/fn.assemble.html) for
+    /// what can be assembled.
+    ///
+    /// # Errors
+    /// Will return Err if the source couldn't be assembled, or if it doesn't fit in memory
+    /// and the overflow policy is `OverflowPolicy::Error`
+    pub fn load_source(mut self, origin: u16, source: &str) -> Result<Self, Error> {
+        let program = assembler::assemble(source, origin)?;
+
+        self.load_words(program.origin, &program.words)?;
+        for (label, address) in program.symbols {
+            self.add_symbol(&label, address);
+        }
+        Ok(self)
+    }
+
+    /// A simulator with no input, output collected into a buffer, and no trace, ready to run
+    /// the program assembled from `source`.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        AND R0, R0, #0
+    ///      LOOP    ADD R0, R0, #1
+    ///              ADD R1, R0, #-5
+    ///              BRn LOOP
+    ///              AND R1, R1, #0
+    ///              STI R1, MCR     ; Stop the clock, as HALT would
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.registers()[0], 5);
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the source couldn't be assembled
+    pub fn from_source(origin: u16, source: &str) -> Result<Self, Error> {
+        Self::new(
+            Reader::Buffer(Cursor::new(Vec::new())),
+            Writer::Buffer(Vec::new()),
+            Tracer::NoTrace,
+        )
+        .load_source(origin, source)
+    }
+
+    /// Copy `words` into memory starting at `address`, and start executing from there
+    fn load_words(&mut self, mut address: u16, words: &[u16]) -> Result<(), Error> {
+        if self.overflow_policy == OverflowPolicy::Error
+            && usize::from(address) + words.len() > self.memory.len()
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Program of {} words at {} runs past the end of memory",
+                    words.len(),
+                    self.formatter.hex(address)
+                ),
+            ));
+        }
+
+        self.pc = address;
+        self.entry_point = address;
+        self.original.clear();
+
+        for &word in words {
+            self.memory[address as usize] = word;
+
+            if self.check_encodings {
+                let canonical = u16::from(Instruction::from(word));
+                if canonical != word {
+                    self.warn(
+                        address,
+                        "unused-bits",
+                        format!(
+                            "Word {} at {} has unused bits set (expected {} for {})",
+                            self.formatter.hex(word),
+                            self.formatter.hex(address),
+                            self.formatter.hex(canonical),
+                            Instruction::from(word).to_assembly(&self.formatter)
+                        ),
+                    );
+                }
+            }
+
+            address = address.wrapping_add(1);
+        }
+
+        Ok(())
+    }
+
+    /// The condition code lives in the low three bits of the PSR, as N (bit 2), Z (bit 1), and
+    /// P (bit 0), so that saving and restoring the PSR also saves and restores the CC.
+    fn update_cc(&mut self, value: u16) {
+        let cc = if value == 0 {
+            0b010
+        } else if value & 0x8000 == 0 {
+            0b001
+        } else {
+            0b100
+        };
+        self.psr = self.psr & !0b111 | cc;
+    }
+
+    fn cc(&self) -> u16 {
+        self.psr & 0b111
+    }
+
+    fn fetch(&mut self) {
+        if self.history_depth > 0 {
+            if self.history.len() == self.history_depth {
+                self.history.pop_front();
+            }
+            self.history.push_back(Delta {
+                registers: self.registers,
+                pc: self.pc,
+                ir: self.ir,
+                ir_address: self.ir_address,
+                psr: self.psr,
+                saved_ssp: self.saved_ssp,
+                saved_usp: self.saved_usp,
+                clock: self.memory[CLK],
+                memory: Vec::new(),
+            });
+        }
+
+        self.watch_hit = None;
+        self.last_branch = None;
+        self.paused = None;
+        self.ir_address = self.pc;
+        self.count_access(self.pc);
+        self.execution_counts[self.pc as usize] += 1;
+        self.ir = self.memory[self.pc as usize];
+        if let Some(ref mut hook) = self.fetch_hook {
+            self.ir = hook(self.pc, self.ir);
+        }
+        self.pc = self.pc.wrapping_add(1);
+    }
+
+    /// The value of each of the general purpose registers
+    #[must_use]
+    pub fn registers(&self) -> [u16; 8] {
+        self.registers
+    }
+
+    /// Set a general purpose register, updating the condition code from its new value as an
+    /// instruction writing to it would.
+    ///
+    /// # Panics
+    /// Will panic if `register` isn't between 0 and 7
+    pub fn write_register(&mut self, register: usize, value: u16) {
+        self.registers[register] = value;
+        self.update_cc(value);
+    }
+
+    /// Set several general purpose registers at once, in order. Only the last write updates
+    /// the condition code, as only the last would be the architected write.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let mut simulator =
+    ///     Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace);
+    ///
+    /// simulator.write_registers(&[(1, 0xFFFF), (2, 0x0000), (3, 0x0005)]);
+    /// assert_eq!(simulator.registers()[1..4], [0xFFFF, 0x0000, 0x0005]);
+    /// assert_eq!(simulator.condition_code(), 'P');
+    ///
+    /// simulator.write_registers(&[(3, 0x0005), (1, 0xFFFF)]);
+    /// assert_eq!(simulator.condition_code(), 'N');
+    /// ```
+    ///
+    /// # Panics
+    /// Will panic if any register isn't between 0 and 7
+    pub fn write_registers(&mut self, updates: &[(usize, u16)]) {
+        updates
+            .iter()
+            .for_each(|&(register, value)| self.registers[register] = value);
+
+        if let Some(&(_, value)) = updates.last() {
+            self.update_cc(value);
+        }
+    }
+
+    /// Where the most recently loaded program starts, which is the origin in its header
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// // A branch which jumps back to itself
+    /// let mut simulator = Simulator::from_source(0x3000, "LOOP BRnzp LOOP").unwrap();
+    ///
+    /// simulator.step();
+    /// assert_eq!(simulator.pc(), 0x3000);
+    /// simulator.write(0x3000, 0x0E05);
+    /// simulator.step();
+    /// assert_eq!(simulator.pc(), 0x3006);
+    /// assert_eq!(simulator.entry_point(), 0x3000);
+    /// ```
+    #[must_use]
+    pub fn entry_point(&self) -> u16 {
+        self.entry_point
+    }
+
+    /// The byte of input the program reads next. That's the byte held in the KBDR once the KBSR
+    /// has said it's ready, or otherwise the one waiting to be delivered the next time the
+    /// program polls the KBSR, such as one typed while the machine was checking for ESC.
+    ///
+    /// A byte which has already been delivered stays in the KBDR until the program reads it,
+    /// however many times the KBSR is polled in the meantime.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::Cursor;
+    ///
+    /// let mut simulator = Simulator::new(
+    ///     Reader::Buffer(Cursor::new(b"ab".to_vec())),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .load_source(
+    ///     0x3000,
+    ///     "        LDI R0, KBSR
+    ///              LDI R0, KBSR
+    ///              LDI R1, KBDR
+    ///              LDI R0, KBSR
+    ///              LDI R2, KBDR
+    ///              AND R3, R3, #0
+    ///              STI R3, MCR
+    ///      KBSR    .FILL xFE00
+    ///      KBDR    .FILL xFE02
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.registers()[1], u16::from(b'a'));
+    /// assert_eq!(simulator.registers()[2], u16::from(b'b'));
+    /// ```
+    #[must_use]
+    pub fn pending_input(&self) -> Option<u8> {
+        if self.keyboard_ready {
+            Some(self.memory[KBDR] as u8)
+        } else {
+            self.pending_input.front().copied()
+        }
+    }
+
+    /// Make `byte` the next byte of input the program sees, ahead of anything else which is
+    /// waiting or still to be read. If the KBSR has already said a byte is ready, `byte`
+    /// replaces the one held in the KBDR.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LDI R0, KBSR
+    ///              LDI R1, KBDR
+    ///              AND R2, R2, #0
+    ///              STI R2, MCR
+    ///      KBSR    .FILL xFE00
+    ///      KBDR    .FILL xFE02
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.set_pending_input(b'y');
+    /// assert_eq!(simulator.pending_input(), Some(b'y'));
+    ///
+    /// // Once the KBSR has been polled the byte waits in the KBDR, where it can still be replaced
+    /// simulator.step();
+    /// assert_eq!(simulator.pending_input(), Some(b'y'));
+    /// simulator.set_pending_input(b'n');
+    /// assert_eq!(simulator.pending_input(), Some(b'n'));
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.registers()[0], 0x8000);
+    /// assert_eq!(simulator.registers()[1], u16::from(b'n'));
+    /// assert_eq!(simulator.pending_input(), None);
+    /// ```
+    pub fn set_pending_input(&mut self, byte: u8) {
+        if self.keyboard_ready {
+            self.memory[KBDR] = u16::from(byte);
+        } else {
+            self.pending_input.push_front(byte);
+        }
+    }
+
+    /// The address of the next instruction to execute
+    #[must_use]
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// The most recently executed instruction
+    #[must_use]
+    pub fn ir(&self) -> u16 {
+        self.ir
+    }
+
+    /// The processor status register, with the condition code in bits 2 to 0, and bit 15 set
+    /// while in user mode. The machine starts in supervisor mode, with the supervisor stack
+    /// at x3000 and the user stack at xFE00 until either has been switched away from.
+    ///
+    /// RTI pops the PC and then the PSR off the supervisor stack in R6, switching R6 over to
+    /// the user stack if that returns to user mode. In user mode RTI instead raises the
+    /// privilege violation exception, through the handler at x0100 if there is one.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Simulator};
+    ///
+    /// let boot = || {
+    ///     Simulator::from_source(
+    ///         0x3000,
+    ///         "        LD R6, SSP
+    ///                  LD R0, UPSR
+    ///                  ADD R6, R6, #-1
+    ///                  STR R0, R6, #0  ; Push the user PSR...
+    ///                  LEA R0, USER
+    ///                  ADD R6, R6, #-1
+    ///                  STR R0, R6, #0  ; ...and the user PC
+    ///                  RTI
+    ///          USER    ADD R1, R1, #0  ; Zero, so the CC no longer matches the pushed PSR
+    ///                  RTI             ; Which isn't allowed in user mode
+    ///          SSP     .FILL x2F00
+    ///          UPSR    .FILL x8004",
+    ///     )
+    ///     .unwrap()
+    /// };
+    ///
+    /// let mut simulator = boot();
+    /// (0..8).for_each(|_| {
+    ///     simulator.step();
+    /// });
+    /// assert_eq!((simulator.pc(), simulator.psr()), (0x3008, 0x8004));
+    /// assert_eq!(simulator.registers()[6], 0xFE00);
+    ///
+    /// simulator.execute();
+    /// assert_eq!(
+    ///     simulator.halt_reason(),
+    ///     Some(HaltReason::Fault(String::from("RTI at 0x3009 was executed in user mode")))
+    /// );
+    ///
+    /// // With a handler for the exception, it's entered in supervisor mode instead
+    /// let mut simulator = boot();
+    /// simulator.write(0x0100, 0x4000);
+    /// (0..10).for_each(|_| {
+    ///     simulator.step();
+    /// });
+    /// assert_eq!((simulator.pc(), simulator.psr() & 0x8000), (0x4000, 0));
+    /// assert_eq!(simulator.registers()[6], 0x2EFE);
+    /// assert_eq!(simulator.memory()[0x2EFE..0x2F00], [0x300A, 0x8002]);
+    /// ```
+    #[must_use]
+    pub fn psr(&self) -> u16 {
+        self.psr
+    }
+
+    /// Take a copy of the state of the machine, which can later be given to
+    /// [`restore`](#method.restore), or saved to disk through serde.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Simulator, Snapshot};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        AND R0, R0, #0
+    ///      LOOP    ADD R0, R0, #1
+    ///              ADD R1, R0, #-5
+    ///              BRn LOOP
+    ///              STI R1, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// for _ in 0..4 {
+    ///     simulator.step();
+    /// }
+    /// let snapshot = simulator.snapshot();
+    ///
+    /// simulator.execute();
+    /// assert!(!simulator.is_running());
+    /// assert_eq!(simulator.registers()[0], 5);
+    ///
+    /// simulator.restore(&snapshot).unwrap();
+    /// assert!(simulator.is_running());
+    /// assert_eq!(simulator.registers(), snapshot.registers);
+    /// assert_eq!(simulator.pc(), 0x3001);
+    /// assert_eq!(simulator.psr(), snapshot.psr);
+    ///
+    /// // Snapshots survive being saved and loaded again
+    /// let saved = serde_json::to_string(&snapshot).unwrap();
+    /// assert_eq!(serde_json::from_str::<Snapshot>(&saved).unwrap(), snapshot);
+    ///
+    /// // But one which doesn't hold all of memory can't be restored
+    /// let mut truncated = snapshot.clone();
+    /// truncated.memory.truncate(0x3000);
+    /// assert!(simulator.restore(&truncated).is_err());
+    /// ```
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memory: self.memory.to_vec(),
+            registers: self.registers,
+            pc: self.pc,
+            ir: self.ir,
+            psr: self.psr,
+            saved_ssp: self.saved_ssp,
+            saved_usp: self.saved_usp,
+        }
+    }
+
+    /// Put the machine back into the state it was in when `snapshot` was taken. Anything the
+    /// program has already read or written stays read or written, while what the simulator
+    /// was tracking about the run since (such as the call stack, a fault, or the history kept
+    /// for [`step_back`](#method.step_back)) is forgotten. The trace carries on from where it
+    /// was, rather than starting again.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let trace = std::env::temp_dir().join(format!("lc3sim_restore_{}.csv", std::process::id()));
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::from((trace.to_str(), Some(vec!["ADD"]), false)).csv(),
+    /// )
+    /// .load_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #1
+    ///              STI R2, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// let snapshot = simulator.snapshot();
+    /// simulator.execute();
+    /// simulator.restore(&snapshot).unwrap();
+    /// simulator.execute();
+    /// drop(simulator);
+    ///
+    /// assert_eq!(
+    ///     std::fs::read_to_string(trace).unwrap(),
+    ///     "pc,ir,disasm,r0,r1,r2,r3,r4,r5,r6,r7,cc\n\
+    ///      12288,4705,\"ADD R1, R1, #1\",0,1,0,0,0,0,0,0,P\n\
+    ///      12288,4705,\"ADD R1, R1, #1\",0,1,0,0,0,0,0,0,P\n"
+    /// );
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the snapshot doesn't hold all of memory
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+        if snapshot.memory.len() != self.memory.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "A snapshot of {} words can't be restored into memory of {} words",
+                    snapshot.memory.len(),
+                    self.memory.len()
+                ),
+            ));
+        }
+
+        self.memory.copy_from_slice(&snapshot.memory);
+        self.registers = snapshot.registers;
+        self.pc = snapshot.pc;
+        self.ir = snapshot.ir;
+        self.psr = snapshot.psr;
+        self.saved_ssp = snapshot.saved_ssp;
+        self.saved_usp = snapshot.saved_usp;
+        self.halt_reason = None;
+        self.paused = None;
+        self.watch_hit = None;
+        self.cycle = None;
+        self.call_stack.clear();
+        self.call_entries.clear();
+        self.muted_until = None;
+        self.faulted = false;
+        self.fault_context = None;
+        self.keyboard_ready = false;
+        self.history.clear();
+
+        Ok(())
+    }
+
+    /// Whether the processor is in supervisor mode, where it starts. A TRAP made from user
+    /// mode pushes the PSR and PC onto the supervisor stack, as an exception does, and stays in
+    /// supervisor mode until the trap routine returns with an RTI.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(0x3000, "TRAP x30").unwrap();
+    /// // A trap routine which only returns
+    /// simulator.write(0x0030, 0x0500);
+    /// simulator.write(0x0500, 0x8000);
+    ///
+    /// assert!(simulator.privileged());
+    /// simulator.write_register(6, 0xFDFF);
+    /// simulator.set_psr(0x8002);
+    /// assert!(!simulator.privileged());
+    ///
+    /// simulator.step();
+    /// assert!(simulator.privileged());
+    /// assert_eq!(simulator.registers()[6], 0x2FFE);
+    /// assert_eq!(simulator.memory()[0x2FFE], 0x3001);
+    /// assert_eq!(simulator.memory()[0x2FFF], 0x8002);
+    ///
+    /// simulator.step();
+    /// assert!(!simulator.privileged());
+    /// assert_eq!(simulator.pc(), 0x3001);
+    /// assert_eq!(simulator.registers()[6], 0xFDFF);
+    /// ```
+    #[must_use]
+    pub fn privileged(&self) -> bool {
+        self.psr & PSR_USER == 0
+    }
+
+    /// Replace the processor status register, which also replaces the condition code.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(0x3000, "ADD R1, R1, #1").unwrap();
+    ///
+    /// simulator.set_psr(0x8004);
+    /// assert_eq!(simulator.condition_code(), 'N');
+    ///
+    /// // The ADD sets P, leaving the rest of the PSR alone
+    /// simulator.step();
+    /// assert_eq!(simulator.condition_code(), 'P');
+    /// assert_eq!(simulator.psr(), 0x8001);
+    /// ```
+    pub fn set_psr(&mut self, psr: u16) {
+        self.psr = psr;
+    }
+
+    /// The disassembly of the instruction at the PC, which is the one the next call to
+    /// [`step`](#method.step) will execute.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #1
+    ///              AND R0, R0, #0",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(simulator.peek_disassembly(), "ADD R1, R1, #1");
+    /// simulator.step();
+    /// assert_eq!(simulator.peek_disassembly(), "AND R0, R0, #0");
+    /// assert_eq!(simulator.peek_disassembly(), "AND R0, R0, #0");
+    /// ```
+    #[must_use]
+    pub fn peek_disassembly(&self) -> String {
+        self.disassemble(self.pc)
+    }
+
+    /// The disassembly of the word at `address`, or a `.FILL` of it if the metadata says
+    /// it's data.
+    #[must_use]
+    pub fn disassemble(&self, address: u16) -> String {
+        let word = self.memory[address as usize];
+
+        match self.metadata.kind_of(address) {
+            Some(RegionKind::Data) | Some(RegionKind::Stack) => {
+                format!(".FILL {}", self.formatter.assembly(word, 4))
+            }
+            _ => Instruction::from(word).to_assembly(&self.formatter),
+        }
+    }
+
+    /// The condition code as one of 'N', 'Z', or 'P', which is also what the trace shows.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let trace = std::env::temp_dir()
+    ///     .join(format!("lc3sim_condition_codes_{}.trace", std::process::id()));
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::from((trace.to_str(), None, false)),
+    /// )
+    /// .load_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #-1
+    ///              AND R1, R1, #0
+    ///              ADD R1, R1, #1
+    ///              AND R0, R0, #0
+    ///              ADD R0, R0, #-7
+    ///              STI R2, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    /// assert_eq!(simulator.condition_code(), 'N');
+    /// drop(simulator);
+    ///
+    /// let codes = std::fs::read_to_string(trace)
+    ///     .unwrap()
+    ///     .lines()
+    ///     .filter_map(|line| line.strip_prefix("Condition Code: "))
+    ///     .collect::<Vec<_>>()
+    ///     .join("");
+    /// assert_eq!(codes, "NZPZNN");
+    /// ```
+    #[must_use]
+    pub fn condition_code(&self) -> char {
+        let cc = self.cc();
+
+        if cc & 0b100 != 0 {
+            'N'
+        } else if cc & 0b010 != 0 {
+            'Z'
+        } else if cc & 0b001 != 0 {
+            'P'
+        } else {
+            // Only possible if the PSR was set without any of the condition code bits
+            'Z'
+        }
+    }
+
+    /// The address `instruction` would access or branch to if it were the next instruction
+    /// executed, given the current PC and registers.
+    ///
+    /// For LDI and STI this is the final address, read from the pointer in memory. Branches
+    /// give their target whether or not the branch would be taken. Instructions which neither
+    /// access memory nor change the PC give `None`.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Instruction, Simulator};
+    ///
+    /// // Leave R2 = x2FFE
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "HERE    LEA R2, HERE
+    ///              ADD R2, R2, #-2",
+    /// )
+    /// .unwrap();
+    /// simulator.step();
+    /// simulator.step();
+    ///
+    /// // PC relative addresses are relative to the incremented PC
+    /// assert_eq!(
+    ///     simulator.effective_address(&Instruction::Load(0, 4)),
+    ///     Some(0x3007)
+    /// );
+    /// assert_eq!(
+    ///     simulator.effective_address(&Instruction::Branch(true, true, true, -3)),
+    ///     Some(0x3000)
+    /// );
+    /// // Register relative addresses are relative to the base register
+    /// assert_eq!(
+    ///     simulator.effective_address(&Instruction::LoadRelative(0, 2, 5)),
+    ///     Some(0x3003)
+    /// );
+    /// assert_eq!(simulator.effective_address(&Instruction::Not(0, 2)), None);
+    /// ```
+    #[must_use]
+    pub fn effective_address(&self, instruction: &Instruction) -> Option<u16> {
+        let pc = self.pc.wrapping_add(1);
+        let pc_relative = |offset: i16| pc.wrapping_add(offset as u16);
+        let base_relative =
+            |base: usize, offset: i16| self.registers[base].wrapping_add(offset as u16);
+
+        match *instruction {
+            Instruction::Branch(_, _, _, offset)
+            | Instruction::Load(_, offset)
+            | Instruction::Store(_, offset)
+            | Instruction::JumpSubroutine(offset)
+            | Instruction::LoadEffectiveAddress(_, offset) => Some(pc_relative(offset)),
+            Instruction::LoadIndirect(_, offset) | Instruction::StoreIndirect(_, offset) => {
+                Some(self.memory[pc_relative(offset) as usize])
+            }
+            Instruction::LoadRelative(_, base, offset)
+            | Instruction::StoreRelative(_, base, offset) => Some(base_relative(base, offset)),
+            Instruction::JumpSubroutineRegister(base) | Instruction::Jump(base) => {
+                Some(self.registers[base])
+            }
+            Instruction::ReturnFromInterrupt if self.privileged() => {
+                Some(self.memory[self.registers[6] as usize])
+            }
+            _ => None,
+        }
+    }
+
+    /// How many instructions have been executed
+    #[must_use]
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions
+    }
+
+    /// How many times an instruction has been fetched from each address, for finding the
+    /// hottest parts of a program.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        AND R1, R1, #0
+    ///              ADD R1, R1, #5
+    ///      LOOP    ADD R0, R0, #2
+    ///              ADD R1, R1, #-1
+    ///              BRp LOOP
+    ///              STI R1, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// let counts = simulator.execution_counts();
+    /// assert_eq!(counts[&0x3001], 1);
+    /// assert_eq!([counts[&0x3002], counts[&0x3003], counts[&0x3004]], [5, 5, 5]);
+    /// assert_eq!(counts.get(&0x3006), None);
+    /// ```
+    #[must_use]
+    pub fn execution_counts(&self) -> HashMap<u16, u64> {
+        self.executed_addresses()
+            .map(|address| (address, self.execution_counts[address as usize]))
+            .collect()
+    }
+
+    /// Every address an instruction has been fetched from, in address order
+    fn executed_addresses(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..=0xFFFF).filter(move |&address: &u16| self.execution_counts[address as usize] != 0)
+    }
+
+    /// A disassembly of only the addresses which have been executed, in address order, with
+    /// how many times each was and the label of any which have one. Code which never ran, and
+    /// data, is left out.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "MAIN    AND R1, R1, #0
+    ///              BRp DEAD        ; Never taken, as R1 is zero
+    ///              STI R1, MCR
+    ///      DEAD    ADD R1, R1, #1
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// let listing = simulator.executed_listing();
+    /// assert_eq!(
+    ///     listing,
+    ///     "MAIN:\n\
+    ///      0x3000  AND R1, R1, #0  ; executed 1 times\n\
+    ///      0x3001  BRp #1  ; executed 1 times\n\
+    ///      0x3002  STI R1, #1  ; executed 1 times\n"
+    /// );
+    /// assert!(!listing.contains("DEAD"));
+    /// assert!(!listing.contains("0x3003"));
+    /// ```
+    #[must_use]
+    pub fn executed_listing(&self) -> String {
+        self.executed_addresses()
+            .map(|address| {
+                format!(
+                    "{}{}  {}  ; executed {} times\n",
+                    self.symbols
+                        .get(&address)
+                        .map_or_else(String::new, |label| format!("{}:\n", label)),
+                    self.formatter.hex(address),
+                    self.disassemble(address),
+                    self.execution_counts[address as usize]
+                )
+            })
+            .collect()
+    }
+
+    /// The lowest and highest addresses the program has stored to, if it has stored anything
+    #[must_use]
+    pub fn touched_memory(&self) -> Option<(u16, u16)> {
+        self.touched
+    }
+
+    /// Every word the program has changed since the last file was loaded, as
+    /// (address, before, after), ordered by address. Device registers aren't included.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// // Store 5 to FIRST, 0 to SECOND (which is already 0), then 5 and 6 to THIRD
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #5
+    ///              ST R1, FIRST
+    ///              ST R2, SECOND
+    ///              ST R1, THIRD
+    ///              ADD R1, R1, #1
+    ///              ST R1, THIRD
+    ///              STI R2, MCR
+    ///      MCR     .FILL xFFFE
+    ///      FIRST   .FILL #0
+    ///      SECOND  .FILL #0
+    ///      THIRD   .FILL #0",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.memory_delta(), vec![(0x3008, 0, 5), (0x300A, 0, 6)]);
+    /// ```
+    #[must_use]
+    pub fn memory_delta(&self) -> Vec<(u16, u16, u16)> {
+        let mut delta = self
+            .original
+            .iter()
+            .filter(|&(&address, &before)| {
+                (address as usize) < DEVICE_REGISTERS && self.memory[address as usize] != before
+            })
+            .map(|(&address, &before)| (address, before, self.memory[address as usize]))
+            .collect::<Vec<_>>();
+        delta.sort_unstable();
+        delta
+    }
+
+    /// Everything about the state of the machine, formatted for a person to read.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #-1
+    ///              ST R1, #5",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.step();
+    /// simulator.step();
+    ///
+    /// let report = simulator.state_report();
+    /// assert!(report.contains("IR: 0x3205 (ST R1, #5)"));
+    /// assert!(report.contains("R1: 0xFFFF (-1)"));
+    /// assert!(report.contains("Instructions executed: 2"));
+    /// assert!(report.contains("Memory written: 0x3007 - 0x3007"));
+    /// assert!(report.contains("Backtrace: empty"));
+    /// ```
+    #[must_use]
+    pub fn state_report(&self) -> String {
+        let hex = |value: u16| self.formatter.hex(value);
+
+        format!(
+            "PC: {}\nIR: {} ({})\nCC: {}\n{}Instructions executed: {}\nMemory written: {}\nBacktrace: {}\n",
+            hex(self.pc),
+            hex(self.ir),
+            Instruction::from(self.ir).to_assembly(&self.formatter),
+            self.condition_code(),
+            self.registers
+                .iter()
+                .enumerate()
+                .map(|(i, &value)| format!("R{}: {} ({})\n", i, hex(value), value as i16))
+                .collect::<String>(),
+            self.instructions,
+            self.touched.map_or_else(
+                || String::from("none"),
+                |(low, high)| format!("{} - {}", hex(low), hex(high))
+            ),
+            if self.call_stack.is_empty() {
+                String::from("empty")
+            } else {
+                self.call_stack
+                    .iter()
+                    .rev()
+                    .map(|&address| hex(address))
+                    .collect::<Vec<_>>()
+                    .join(" <- ")
+            }
+        )
+    }
+
+    /// Whether the machine's clock is still running
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.memory[CLK] & 0x8000 != 0
+    }
+
+    /// The address and disassembly of the instruction which caused the machine to stop on
+    /// an error, or None if it hasn't.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R0, R0, #1
+    ///              .FILL xD123",
+    /// )
+    /// .unwrap()
+    /// .with_binary_output(true);
+    ///
+    /// simulator.execute();
+    ///
+    /// assert_eq!(
+    ///     simulator.fault_context(),
+    ///     Some((0x3001, String::from(".FILL xD123")))
+    /// );
+    /// assert_eq!(simulator.warnings(), ["Illegal opcode 0xD123 at 0x3001"]);
+    /// assert_eq!(
+    ///     simulator.display().contents(),
+    ///     b"\n--- Illegal opcode 0xD123 at 0x3001 ---\n"
+    /// );
+    /// ```
+    ///
+    /// An illegal opcode is only a fault when there's no handler for the illegal opcode
+    /// exception at x0101. With one, the PSR and PC are pushed onto the supervisor stack and
+    /// the handler is entered in supervisor mode.
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(0x3000, ".FILL xD000").unwrap();
+    /// simulator.write(0x0101, 0x1000);
+    /// simulator.step();
+    ///
+    /// assert_eq!(simulator.pc(), 0x1000);
+    /// assert!(simulator.privileged());
+    /// assert_eq!(simulator.fault_context(), None);
+    /// ```
+    #[must_use]
+    pub fn fault_context(&self) -> Option<(u16, String)> {
+        self.fault_context.clone()
+    }
+
+    /// Give meaning to part of the reserved opcode (`0xD000`), for experimenting with new
+    /// instructions. Whenever a reserved instruction with `subop` in bits 5 to 3 is executed,
+    /// `handler` is called with the instruction, leaving bits 11 to 6 and 2 to 0 free for
+    /// operands. Any reserved instruction without a handler is still an illegal opcode.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        AND R0, R0, #0
+    ///              ADD R0, R0, #6
+    ///              AND R1, R1, #0
+    ///              ADD R1, R1, #7
+    ///              .FILL xD409     ; MUL R2, R0, R1
+    ///              AND R3, R3, #0
+    ///              STI R3, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// // MUL DR, SR1, SR2 as 1101 DR SR1 001 SR2
+    /// simulator.register_extension(1, |simulator, ir| {
+    ///     let registers = simulator.registers();
+    ///     let product = registers[usize::from(ir >> 6 & 0x7)]
+    ///         .wrapping_mul(registers[usize::from(ir & 0x7)]);
+    ///     simulator.write_register(usize::from(ir >> 9 & 0x7), product);
+    /// });
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.registers()[2], 42);
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::Halted));
+    /// ```
+    ///
+    /// # Panics
+    /// Will panic if `subop` isn't between 0 and 7
+    pub fn register_extension<F: Fn(&mut Simulator, u16) + 'static>(
+        &mut self,
+        subop: u16,
+        handler: F,
+    ) {
+        assert!(subop < 8, "There is no reserved sub-opcode {}", subop);
+        self.extensions.insert(subop, Rc::new(handler));
+    }
+
+    /// Why the machine stopped, or None while it's still running. Execution which paused at a
+    /// breakpoint gives that, until the machine moves on.
+    #[must_use]
+    pub fn halt_reason(&self) -> Option<HaltReason> {
+        if self.is_running() {
+            self.paused.clone()
+        } else {
+            Some(self.halt_reason.clone().unwrap_or(HaltReason::Halted))
+        }
+    }
+
+    /// Stop execution before the instruction at `address` is executed.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Simulator};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        AND R0, R0, #0
+    ///      LOOP    ADD R0, R0, #1
+    ///              ADD R1, R0, #-3
+    ///              BRn LOOP
+    ///              ADD R2, R2, #1
+    ///              ADD R3, R3, #1
+    ///              STI R1, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.add_breakpoint(0x3005);
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.pc(), 0x3005);
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::Breakpoint(0x3005)));
+    /// assert_eq!(simulator.registers()[2], 1);
+    /// assert_eq!(simulator.registers()[3], 0);
+    ///
+    /// // The breakpoint stays set, and continuing moves past it
+    /// simulator.execute();
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::Halted));
+    /// assert!(simulator.breakpoints().len() == 1);
+    /// ```
+    pub fn add_breakpoint(&mut self, address: u16) {
+        if self.breakpoints.insert(address) {
+            self.list_breakpoint(BreakpointKind::Address(address));
+        }
+    }
+
+    /// Remove a breakpoint, returning whether one was set at `address`.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoint_list
+            .retain(|info| info.kind != BreakpointKind::Address(address));
+        self.breakpoints.remove(&address)
+    }
+
+    /// Every breakpoint and watchpoint currently set, in the order they were added.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{BreakpointInfo, BreakpointKind, Reader, Simulator, Tracer, Writer};
+    ///
+    /// let mut simulator =
+    ///     Simulator::new(Reader::from(""), Writer::Buffer(Vec::new()), Tracer::NoTrace);
+    ///
+    /// simulator.add_breakpoint(0x3005);
+    /// simulator.break_on_register_write(5);
+    /// simulator.add_region_watchpoint(0x4000, 0x40FF);
+    ///
+    /// assert_eq!(
+    ///     simulator.breakpoints(),
+    ///     [
+    ///         BreakpointInfo { id: 1, kind: BreakpointKind::Address(0x3005) },
+    ///         BreakpointInfo { id: 2, kind: BreakpointKind::RegisterWrite(5) },
+    ///         BreakpointInfo { id: 3, kind: BreakpointKind::Region(0x4000, 0x40FF) },
+    ///     ]
+    /// );
+    ///
+    /// assert!(simulator.remove_breakpoint_by_id(2));
+    /// assert!(!simulator.remove_breakpoint_by_id(2));
+    /// assert_eq!(
+    ///     simulator.breakpoints().iter().map(|info| info.id).collect::<Vec<_>>(),
+    ///     [1, 3]
+    /// );
+    ///
+    /// simulator.clear_breakpoints();
+    /// assert!(simulator.breakpoints().is_empty());
+    /// ```
+    #[must_use]
+    pub fn breakpoints(&self) -> Vec<BreakpointInfo> {
+        self.breakpoint_list.clone()
+    }
+
+    /// Remove the breakpoint or watchpoint with the id `id`, returning whether there was one.
+    pub fn remove_breakpoint_by_id(&mut self, id: usize) -> bool {
+        let position = match self.breakpoint_list.iter().position(|info| info.id == id) {
+            Some(position) => position,
+            None => return false,
+        };
+
+        match self.breakpoint_list.remove(position).kind {
+            BreakpointKind::Address(address) => {
+                self.breakpoints.remove(&address);
+            }
+            BreakpointKind::RegisterWrite(register) => {
+                self.register_watches.remove(&register);
+            }
+            BreakpointKind::Region(start, end) => {
+                if let Some(watch) = self
+                    .region_watches
+                    .iter()
+                    .position(|&watch| watch == (start, end))
+                {
+                    self.region_watches.remove(watch);
+                }
+            }
+            BreakpointKind::Watch(address, _) => {
+                self.watches.remove(&address);
+            }
+        }
+
+        true
+    }
+
+    /// Remove every breakpoint and watchpoint
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.register_watches.clear();
+        self.region_watches.clear();
+        self.watches.clear();
+        self.breakpoint_list.clear();
+    }
+
+    fn list_breakpoint(&mut self, kind: BreakpointKind) {
+        self.breakpoint_list.push(BreakpointInfo {
+            id: self.next_breakpoint_id,
+            kind,
+        });
+        self.next_breakpoint_id += 1;
+    }
+
+    /// Stop the machine as soon as an instruction in user code (at or above x3000) writes to
+    /// `register`, reporting which instruction it was in the warnings.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Simulator;
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #1
+    ///              ADD R5, R5, #1
+    ///              ADD R1, R1, #1",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.break_on_register_write(5);
+    /// simulator.execute();
+    ///
+    /// assert!(!simulator.is_running());
+    /// assert_eq!(simulator.pc(), 0x3002);
+    /// assert_eq!(simulator.registers()[1], 1);
+    /// assert_eq!(
+    ///     simulator.warnings(),
+    ///     ["R5 was written by ADD R5, R5, #1 at 0x3001"]
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    /// Will panic if `register` isn't between 0 and 7
+    pub fn break_on_register_write(&mut self, register: usize) {
+        assert!(register < 8, "There is no register R{}", register);
+        if self.register_watches.insert(register) {
+            self.list_breakpoint(BreakpointKind::RegisterWrite(register));
+        }
+    }
+
+    /// Pause execution as soon as anything is stored between `start` and `end` (inclusive),
+    /// such as to catch a loop running off the end of a buffer.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Simulator, WatchHit, WatchKind};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R1, BUFFER
+    ///              AND R0, R0, #0
+    ///      LOOP    ADD R0, R0, #1
+    ///              STR R0, R1, #0
+    ///              ADD R1, R1, #1
+    ///              BRnzp LOOP
+    ///      BUFFER  .FILL x3FFC",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.write(0x4000, 0x1234);
+    /// simulator.add_region_watchpoint(0x4000, 0x40FF);
+    /// simulator.execute();
+    ///
+    /// assert_eq!(
+    ///     simulator.watch_hit(),
+    ///     Some(WatchHit {
+    ///         address: 0x4000,
+    ///         kind: WatchKind::Write,
+    ///         old: 0x1234,
+    ///         new: 5,
+    ///         pc: 0x3003,
+    ///     })
+    /// );
+    /// assert_eq!(simulator.pc(), 0x3004);
+    /// assert!(simulator.is_running());
+    /// ```
+    pub fn add_region_watchpoint(&mut self, start: u16, end: u16) {
+        self.region_watches.push((start, end));
+        self.list_breakpoint(BreakpointKind::Region(start, end));
+    }
+
+    /// Pause execution as soon as the program accesses `address` in a way `kind` covers. The
+    /// device registers are watched too, so a watchpoint on the KBSR pauses on polling the
+    /// keyboard, and one on the DDR pauses on output. Watching an address which is already
+    /// watched changes the kind of access it pauses on, keeping its id.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Simulator, WatchHit, WatchKind};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LD R0, COUNT
+    ///              ADD R0, R0, #1
+    ///              ST R0, COUNT
+    ///              AND R1, R1, #0
+    ///              STI R1, MCR
+    ///      COUNT   .FILL #41
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.add_watchpoint(0x3005, WatchKind::Write);
+    /// simulator.execute();
+    ///
+    /// let hit = WatchHit {
+    ///     address: 0x3005,
+    ///     kind: WatchKind::Write,
+    ///     old: 41,
+    ///     new: 42,
+    ///     pc: 0x3002,
+    /// };
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::Watchpoint(hit)));
+    /// assert_eq!(simulator.pc(), 0x3003);
+    ///
+    /// // Reads of the device registers are caught as well
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        LDI R0, KBSR
+    ///      KBSR    .FILL xFE00",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.add_watchpoint(0xFE00, WatchKind::Write);
+    /// simulator.add_watchpoint(0xFE00, WatchKind::ReadWrite);
+    /// assert_eq!(simulator.breakpoints().len(), 1);
+    /// simulator.execute();
+    ///
+    /// assert_eq!(simulator.watch_hit().map(|hit| hit.kind), Some(WatchKind::Read));
+    /// assert_eq!(simulator.watch_hit().map(|hit| hit.pc), Some(0x3000));
+    /// ```
+    pub fn add_watchpoint(&mut self, address: u16, kind: WatchKind) {
+        if self.watches.insert(address, kind).is_none() {
+            self.list_breakpoint(BreakpointKind::Watch(address, kind));
+        } else if let Some(info) = self.breakpoint_list.iter_mut().find(
+            |info| matches!(info.kind, BreakpointKind::Watch(watched, _) if watched == address),
+        ) {
+            info.kind = BreakpointKind::Watch(address, kind);
+        }
+    }
+
+    /// The access to watched memory which paused execution, if the last instruction did one
+    #[must_use]
+    pub fn watch_hit(&self) -> Option<WatchHit> {
+        self.watch_hit
+    }
+
+    /// Whether execution is currently stopped at a breakpoint
+    #[must_use]
+    pub fn at_breakpoint(&self) -> bool {
+        self.breakpoints.contains(&self.pc)
+    }
+
+    /// The instruction just executed, fetched from `address`, with the label it refers to in
+    /// place of its PC offset if that address has one
+    fn labelled_assembly(&self, address: u16) -> String {
+        let instruction = Instruction::from(self.ir);
+        let assembly = instruction.to_assembly(&self.formatter);
+
+        match instruction.target(address) {
+            // A PC offset is always the last operand
+            Some(target) => match (self.symbols.get(&target), assembly.rfind('#')) {
+                (Some(label), Some(offset)) => format!(
+                    "{}{} ; {}",
+                    &assembly[..offset],
+                    label,
+                    self.formatter.hex(target)
+                ),
+                _ => assembly,
+            },
+            None => assembly,
+        }
+    }
+
+    /// Trace the instruction just executed, given the address it was fetched from and the
+    /// registers as they were before it executed.
+    fn trace(&mut self, address: u16, registers: [u16; 8]) {
+        let wanted = self.tracer.wants(self.ir >> 12 & 0b1111, self.pc);
+        let open = self
+            .trace_gate
+            .is_none_or(|gate| self.memory[gate as usize] != 0);
+
+        if open && (wanted || self.ring_size != 0) {
+            let text = match self.trace_format {
+                TraceFormat::Full => format!(
+                    "After executing instruction: {}\n{}Program Counter: {}{}\nCondition Code: {}\n===================================\n",
+                    self.formatter.hex(self.ir),
+                    (0..8)
+                        .map(|i| format!(
+                            "Register {}: {}\n",
+                            i,
+                            self.formatter.hex(self.registers[i])
+                        ))
+                        .collect::<String>(),
+                    self.formatter.hex(self.pc),
+                    self.label_of(self.pc)
+                        .map_or_else(String::new, |label| format!(" ({})", label)),
+                    self.condition_code()
+                ),
+                TraceFormat::Pretty => format!(
+                    "{}\n",
+                    format!(
+                        "{}  {}  {:<24}{}",
+                        self.formatter.assembly(address, 4),
+                        self.formatter.digits(self.ir, 4),
+                        self.labelled_assembly(address),
+                        (0..8)
+                            .filter(|&i| registers[i] != self.registers[i])
+                            .map(|i| format!(
+                                "R{}: {} -> {}",
+                                i,
+                                self.formatter.assembly(registers[i], 4),
+                                self.formatter.assembly(self.registers[i], 4)
+                            ))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                    .trim_end()
+                ),
+            };
+            let record = TraceRecord {
+                pc: address,
+                ir: self.ir,
+                disasm: Instruction::from(self.ir).to_assembly(&self.formatter),
+                registers: self.registers,
+                cc: self.condition_code(),
+                text,
+            };
+
+            if wanted {
+                self.tracer.trace(&record);
+            }
+
+            if self.ring_size != 0 {
+                if self.ring.len() == self.ring_size {
+                    self.ring.pop_front();
+                }
+                self.ring.push_back(record.text);
+            }
+        }
+
+        if let Some((before, after, outcome)) = self.prediction.take() {
+            if open && self.trace_prediction {
+                let predicted = before.predict();
+                self.tracer.note(&format!(
+                    "{}  {}  predicted {}, was {} ({})  {} -> {}\n",
+                    self.formatter.assembly(address, 4),
+                    Instruction::from(self.ir).to_assembly(&self.formatter),
+                    predicted,
+                    outcome,
+                    if predicted == outcome {
+                        "correct"
+                    } else {
+                        "mispredicted"
+                    },
+                    before,
+                    after
+                ));
+            }
+        }
+    }
+
+    /// Execute a single instruction, returning whether the machine is still running
+    /// afterwards, along with the instruction and which way it went if it was a branch.
+    /// Stepping onto a breakpoint pauses there, as [`halt_reason`](#method.halt_reason)
+    /// shows.
+    ///
+    /// If an instruction was part way through being executed by
+    /// [`step_cycle`](#method.step_cycle), only the rest of it is executed.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Branch, HaltReason, Instruction, Operand, Simulator, StepResult};
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #1
+    ///              BRz DONE
+    ///      DONE    STI R2, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(
+    ///     simulator.step(),
+    ///     StepResult {
+    ///         running: true,
+    ///         instruction: Some(Instruction::Add(1, 1, Operand::Immediate(1))),
+    ///         branch: None,
+    ///     }
+    /// );
+    /// assert_eq!(simulator.halt_reason(), None);
+    ///
+    /// simulator.add_breakpoint(0x3002);
+    /// assert_eq!(simulator.step().branch, Some(Branch::NotTaken));
+    /// assert_eq!(simulator.halt_reason(), Some(HaltReason::Breakpoint(0x3002)));
+    /// assert!(!simulator.step().running);
+    /// assert_eq!(simulator.step().instruction, None);
+    /// ```
+    pub fn step(&mut self) -> StepResult {
+        let mut instruction = None;
+
+        if self.cycle.is_some() {
+            while self.step_cycle().is_some() && self.cycle.is_some() {}
+            instruction = Some(Instruction::from(self.ir));
+        } else if self.memory[CLK] & 0x8000 != 0 {
+            let address = self.pc;
+            let registers = self.registers;
+
+            self.fetch();
+            self.evaluate();
+            self.complete(address, registers);
+            instruction = Some(Instruction::from(self.ir));
+        }
+
+        if instruction.is_some()
+            && self.is_running()
+            && self.paused.is_none()
+            && self.at_breakpoint()
+        {
+            self.paused = Some(HaltReason::Breakpoint(self.pc));
+        }
+
+        StepResult {
+            running: self.is_running(),
+            instruction,
+            branch: instruction.and(self.last_branch),
         }
     }
 
-    #[must_use]
-    pub fn with_operating_system(self, file: &str) -> Self {
-        self.load(file).expect("Unable to load Operating System")
+    /// Undo the last instruction executed, returning whether there was one remembered to undo.
+    /// Only as many instructions as [`with_history`](#method.with_history) asked for are
+    /// remembered.
+    pub fn step_back(&mut self) -> bool {
+        let delta = match self.history.pop_back() {
+            Some(delta) => delta,
+            None => return false,
+        };
+
+        for &(address, value) in delta.memory.iter().rev() {
+            self.memory[address as usize] = value;
+        }
+        self.registers = delta.registers;
+        self.pc = delta.pc;
+        self.ir = delta.ir;
+        self.ir_address = delta.ir_address;
+        self.psr = delta.psr;
+        self.saved_ssp = delta.saved_ssp;
+        self.saved_usp = delta.saved_usp;
+        self.memory[CLK] = delta.clock;
+        self.cycle = None;
+        self.halt_reason = None;
+        self.paused = None;
+        self.watch_hit = None;
+
+        true
     }
 
-    /// Load the specified file into the simulator.
+    /// Advance the machine by a single cycle of the current instruction, returning the phase
+    /// that just happened, or None if the machine isn't running.
     ///
-    /// # Errors
-    /// Will return Err if the supplied file was unable to be read from
-    pub fn load(mut self, file: &str) -> Result<Self, Error> {
-        let mut file = File::open(file)?;
+    /// The PC and IR change in the fetch phase, but everything else the instruction does
+    /// happens together in its last phase, as though it were written back from latches.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Phase, Simulator};
+    ///
+    /// // Step through a whole instruction, giving the phases it went through
+    /// fn phases(simulator: &mut Simulator) -> Vec<Phase> {
+    ///     let count = simulator.instruction_count();
+    ///     let mut phases = Vec::new();
+    ///     while simulator.instruction_count() == count {
+    ///         phases.push(simulator.step_cycle().unwrap());
+    ///     }
+    ///     phases
+    /// }
+    ///
+    /// let mut simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #5
+    ///              LD R2, VALUE
+    ///              TRAP x40
+    ///      VALUE   .FILL x1234",
+    /// )
+    /// .unwrap();
+    /// simulator.write(0x0040, 0x4000);
+    ///
+    /// assert_eq!(
+    ///     phases(&mut simulator),
+    ///     [Phase::Fetch, Phase::Decode, Phase::OperandRead, Phase::Alu, Phase::Writeback]
+    /// );
+    /// assert_eq!(simulator.registers()[1], 5);
+    ///
+    /// let memory = [
+    ///     Phase::Fetch,
+    ///     Phase::Decode,
+    ///     Phase::OperandRead,
+    ///     Phase::Alu,
+    ///     Phase::Memory,
+    ///     Phase::Writeback,
+    /// ];
+    /// assert_eq!(phases(&mut simulator), memory);
+    /// assert_eq!(simulator.registers()[2], 0x1234);
+    ///
+    /// assert_eq!(phases(&mut simulator), memory);
+    /// assert_eq!(simulator.pc(), 0x4000);
+    /// assert_eq!(simulator.registers()[7], 0x3003);
+    /// assert_eq!(simulator.instruction_count(), 3);
+    /// ```
+    pub fn step_cycle(&mut self) -> Option<Phase> {
+        let (done, address, registers) = match self.cycle {
+            Some((done, address, registers)) => (done + 1, address, registers),
+            None if self.is_running() => {
+                let address = self.pc;
+                let registers = self.registers;
+                self.fetch();
+                (0, address, registers)
+            }
+            None => return None,
+        };
 
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
+        let phases = Phase::of(self.ir);
+        if done + 1 == phases.len() {
+            self.cycle = None;
+            self.evaluate();
+            self.complete(address, registers);
+        } else {
+            self.cycle = Some((done, address, registers));
+        }
 
-        let mut address = u16::from(buffer[0]) << 8 | u16::from(buffer[1]);
+        Some(phases[done])
+    }
 
-        self.pc = address;
+    /// Finish off an instruction that's just been evaluated
+    fn complete(&mut self, address: u16, registers: [u16; 8]) {
+        if !self.register_watches.is_empty() && address >= USER_SPACE {
+            let instruction = Instruction::from(self.ir);
 
-        (2..buffer.len()).step_by(2).for_each(|i| {
-            self.memory[address as usize] = u16::from(buffer[i]) << 8 | u16::from(buffer[i + 1]);
-            address += 1;
-        });
+            if let Some(register) = instruction
+                .destination()
+                .filter(|register| self.register_watches.contains(register))
+            {
+                self.fault(
+                    "register-write",
+                    format!(
+                        "R{} was written by {} at {}",
+                        register,
+                        instruction.to_assembly(&self.formatter),
+                        self.formatter.hex(address)
+                    ),
+                );
+            }
+        }
 
-        Ok(self)
+        if self.check_null_execution && address >= USER_SPACE && self.in_trap_table(self.pc) {
+            self.fault(
+                "null-execution",
+                format!(
+                    "{} at {} sent the PC to {}, in the trap vector table",
+                    Instruction::from(self.ir).to_assembly(&self.formatter),
+                    self.formatter.hex(address),
+                    self.formatter.hex(self.pc)
+                ),
+            );
+            self.halt_reason = Some(HaltReason::NullExecution);
+        }
+
+        if self.executed.is_some() {
+            self.record_execution(address, registers);
+        }
+
+        self.instructions += 1;
+        self.trace(address, registers);
+
+        if self.interrupt_interval != 0 && self.instructions.is_multiple_of(self.interrupt_interval)
+        {
+            self.check_for_interrupt();
+        }
+
+        if !self.is_running() {
+            self.release_output();
+        }
     }
 
-    fn update_cc(&mut self, value: u16) {
-        self.cc = if value == 0 {
-            0b010
-        } else if value & 0x8000 == 0 {
-            0b001
-        } else {
-            0b100
+    /// Record the instruction just executed, along with what a replay of it needs: the value
+    /// loaded, the address stored to, or the return address saved in R7.
+    fn record_execution(&mut self, address: u16, registers: [u16; 8]) {
+        let pc_relative = |offset: i16| address.wrapping_add(1).wrapping_add(offset as u16);
+
+        let value = match Instruction::from(self.ir) {
+            Instruction::Load(dr, _)
+            | Instruction::LoadRelative(dr, _, _)
+            | Instruction::LoadIndirect(dr, _)
+            | Instruction::LoadEffectiveAddress(dr, _) => self.registers[dr],
+            Instruction::JumpSubroutine(_)
+            | Instruction::JumpSubroutineRegister(_)
+            | Instruction::Trap(_) => self.registers[7],
+            Instruction::Store(_, offset) => pc_relative(offset),
+            Instruction::StoreRelative(_, base, offset) => {
+                registers[base].wrapping_add(offset as u16)
+            }
+            Instruction::StoreIndirect(_, offset) => self.memory[pc_relative(offset) as usize],
+            _ => 0,
         };
+
+        if let Some(ref mut executed) = self.executed {
+            executed.push((address, self.ir, value));
+        }
     }
 
-    fn fetch(&mut self) {
-        self.ir = self.memory[self.pc as usize];
-        self.pc = self.pc.wrapping_add(1);
+    /// Once the machine stops, send any output that was held back to the display if the
+    /// program halted cleanly, or throw it away if it didn't.
+    fn release_output(&mut self) {
+        if let Some(held) = self.held_output.take() {
+            if self.halt_reason() == Some(HaltReason::Halted) {
+                held.into_iter().for_each(|value| self.output(value));
+            }
+            self.held_output = Some(Vec::new());
+        }
     }
 
-    fn trace(&mut self) {
-        if self.tracer.wants(self.ir >> 12 & 0b1111, self.pc) {
-            self.tracer.trace(
-                format!(
-                    "After executing instruction: 0x{:04X}\n{}Program Counter: 0x{:04X}\nCondition Code: {}\n===================================\n",
-                    self.ir,
-                    (0..8)
-                        .map(|i| format!("Register {}: 0x{:04X}\n", i, self.registers[i]))
-                        .collect::<String>(),
-                    self.pc,
-                    if self.cc & 0b100 != 0 { 'N' } else if self.cc & 0b010 == 0 { 'P' } else { 'Z' }
-                )
-                .as_ref(),
+    /// Step through the program, checking the state of the machine after each instruction
+    /// against the next of `expected`, such as a trace recorded from a reference simulator.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{ExpectedState, Simulator};
+    ///
+    /// let simulator = Simulator::from_source(
+    ///     0x3000,
+    ///     "        AND R0, R0, #0
+    ///              ADD R0, R0, #3
+    ///              ADD R1, R0, #-4
+    ///              NOT R2, R1
+    ///              STI R2, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// let state = |pc, r0, r1, r2, condition_code| ExpectedState {
+    ///     pc,
+    ///     registers: [r0, r1, r2, 0, 0, 0, 0, 0],
+    ///     condition_code,
+    /// };
+    ///
+    /// simulator.assert_matches(
+    ///     vec![
+    ///         state(0x3001, 0, 0, 0, 'Z'),
+    ///         state(0x3002, 3, 0, 0, 'P'),
+    ///         state(0x3003, 3, 0xFFFF, 0, 'N'),
+    ///         state(0x3004, 3, 0xFFFF, 0, 'Z'),
+    ///         state(0x3005, 3, 0xFFFF, 0, 'Z'),
+    ///     ]
+    ///     .into_iter(),
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    /// Will panic at the first instruction after which the machine isn't in the expected
+    /// state, or if it halts before every expected state has been checked
+    pub fn assert_matches<I: Iterator<Item = ExpectedState>>(mut self, expected: I) {
+        for (i, expected) in expected.enumerate() {
+            assert!(
+                self.is_running(),
+                "The machine halted after {} instructions, but more were expected",
+                i
+            );
+
+            let address = self.pc;
+            self.step();
+
+            let actual = ExpectedState {
+                pc: self.pc,
+                registers: self.registers,
+                condition_code: self.condition_code(),
+            };
+            assert!(
+                actual == expected,
+                "Instruction {} ({} at {}) diverged: expected {:?}, but found {:?}",
+                i + 1,
+                Instruction::from(self.ir).to_assembly(&self.formatter),
+                self.formatter.hex(address),
+                expected,
+                actual
             );
         }
     }
 
-    pub fn execute(mut self) {
-        while self.read(CLK as u16) & 0x8000 != 0 {
-            self.fetch();
-            self.step();
-            self.trace();
+    /// Run the machine until it halts, until it reaches a breakpoint, or until watched memory
+    /// is accessed, giving the reason it stopped.
+    ///
+    /// A breakpoint on the instruction about to be executed stops execution before it runs,
+    /// unless execution stopped there already, so calling this while stopped at a breakpoint
+    /// continues on to the next one.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{HaltReason, Reader, Simulator, Tracer, Writer};
+    ///
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from("a"),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .load_source(
+    ///     0x3000,
+    ///     "LOOP    LDI R0, KBSR
+    ///              BRzp LOOP
+    ///              LDI R1, KBDR
+    ///              BRnzp LOOP
+    ///      KBSR    .FILL xFE00
+    ///      KBDR    .FILL xFE02",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.add_breakpoint(0x3000);
+    /// simulator.add_breakpoint(0x3002);
+    /// assert_eq!(simulator.execute(), HaltReason::Breakpoint(0x3000));
+    /// assert_eq!(simulator.instruction_count(), 0);
+    /// assert_eq!(simulator.execute(), HaltReason::Breakpoint(0x3002));
+    /// assert_eq!(simulator.execute(), HaltReason::Breakpoint(0x3000));
+    /// assert_eq!(simulator.execute(), HaltReason::OutOfInput);
+    /// assert_eq!(simulator.registers()[1], u16::from(b'a'));
+    /// ```
+    pub fn execute(&mut self) -> HaltReason {
+        if self.is_running()
+            && self.at_breakpoint()
+            && self.paused != Some(HaltReason::Breakpoint(self.pc))
+        {
+            self.paused = Some(HaltReason::Breakpoint(self.pc));
+        } else {
+            while self.step().running && self.paused.is_none() {}
         }
+
+        // Stopping early at a breakpoint or watchpoint always leaves a reason behind
+        self.halt_reason().unwrap_or(HaltReason::Halted)
     }
 
     fn read(&mut self, address: u16) -> u16 {
-        match address as usize {
-            DDR => 0x0000,
+        self.access_cache(address);
+
+        let value = match address as usize {
+            DDR => {
+                if self.check_devices {
+                    self.device_warning("read from the DDR, which is write only");
+                }
+
+                match self.ddr_read {
+                    DdrRead::Fixed(value) => value,
+                    DdrRead::LastWritten => self.last_output,
+                }
+            }
             KBSR => {
-                let mut buf = [0; 1];
-                match self.input.read(&mut buf) {
-                    Ok(x) if x != 0 => {
-                        self.memory[KBDR] = u16::from(buf[0]);
-                        0x8000
-                    }
-                    Err(ref e) if e.kind() == ErrorKind::Interrupted => {
-                        println!("\r\n--- ESC pressed. Quitting simulator ---\r");
-                        self.memory[CLK] = 0x0000;
-                        0x0000
-                    }
-                    Err(_) => {
-                        println!(
-                            "\r\n--- Program requires more input than provided in the input file ---\r"
-                        );
-                        self.memory[CLK] = 0x0000;
-                        0x0000
-                    }
-                    _ => 0x0000,
+                self.io_wait_cycles += self.device_latency.keyboard;
+
+                self.poll_keyboard()
+            }
+            KBDR => {
+                self.keyboard_ready = false;
+                self.memory[KBDR]
+            }
+            DSR => {
+                self.io_wait_cycles += self.device_latency.display;
+
+                // A character which previously failed to display is retried each time the
+                // program checks whether the display is ready.
+                if let Some(value) = self.pending_output.take() {
+                    self.memory[DSR] = 0x8000;
+                    self.output(value);
                 }
+                self.memory[DSR]
             }
             addr => self.memory[addr],
+        };
+
+        self.observe_access(address, WatchKind::Read, value, value);
+        value
+    }
+
+    /// Trace an access to `address` if it's in the traced range, and pause execution if it's
+    /// watched for this kind of access
+    fn observe_access(&mut self, address: u16, kind: WatchKind, old: u16, new: u16) {
+        let traced = self
+            .memory_trace
+            .is_some_and(|(start, end)| (start..=end).contains(&address))
+            && self.tracer.wants(self.ir >> 12 & 0b1111, self.pc)
+            && self
+                .trace_gate
+                .is_none_or(|gate| self.memory[gate as usize] != 0);
+
+        if traced {
+            let access = match kind {
+                WatchKind::Read => format!(
+                    "{}  read   {}  {}\n",
+                    self.formatter.assembly(self.pc_of_ir(), 4),
+                    self.formatter.assembly(address, 4),
+                    self.formatter.assembly(new, 4)
+                ),
+                _ => format!(
+                    "{}  store  {}  {} -> {}\n",
+                    self.formatter.assembly(self.pc_of_ir(), 4),
+                    self.formatter.assembly(address, 4),
+                    self.formatter.assembly(old, 4),
+                    self.formatter.assembly(new, 4)
+                ),
+            };
+            self.tracer.note(&access);
+        }
+
+        let watched = self
+            .region_watches
+            .iter()
+            .any(|&(start, end)| kind == WatchKind::Write && (start..=end).contains(&address))
+            || self
+                .watches
+                .get(&address)
+                .is_some_and(|watch| watch.covers(kind));
+
+        if watched {
+            let hit = WatchHit {
+                address,
+                kind,
+                old,
+                new,
+                pc: self.pc_of_ir(),
+            };
+            self.watch_hit = Some(hit);
+            self.paused = Some(HaltReason::Watchpoint(hit));
         }
     }
 
     pub fn write(&mut self, address: u16, value: u16) {
         match address as usize {
             DDR => {
+                let pc = self.pc_of_ir();
+                if let Some(ref mut provenance) = self.provenance {
+                    provenance.push((pc, value as u8));
+                }
+
+                self.last_output = value;
                 self.memory[DDR] = 0x0000;
                 self.memory[DSR] = 0x8000;
-                let value = value as u8 as char;
-                let _ = self
-                    .display
-                    .write(format!("{}{}", if value == '\n' { "\r" } else { "" }, value).as_ref())
-                    .unwrap_or_else(|_| {
-                        self.memory[DSR] = 0;
-                        0
-                    });
+                self.output(value as u8);
             }
             addr => {
                 self.memory[addr] = value;
@@ -181,7 +5302,465 @@ impl Simulator {
         }
     }
 
-    fn step(&mut self) {
+    /// Check the keyboard for a character, as reading the KBSR does, giving the new KBSR. A
+    /// character stays in the KBDR until the program reads it, so polling again in the meantime
+    /// doesn't take any more input.
+    fn poll_keyboard(&mut self) -> u16 {
+        if self.keyboard_ready {
+            return 0x8000;
+        }
+
+        let mut buf = [0; 1];
+        let input = match self.pending_input.pop_front() {
+            Some(byte) => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            None => self.input.read(&mut buf),
+        };
+
+        match input {
+            Ok(x) if x != 0 => {
+                self.memory[KBDR] = u16::from(buf[0]);
+                self.keyboard_ready = true;
+                if self.echo && self.input.is_keyboard() {
+                    self.output(buf[0]);
+                }
+                0x8000
+            }
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {
+                self.interrupt();
+                0x0000
+            }
+            Err(_) => match self.eof_policy {
+                EofPolicy::Block if self.input.is_keyboard() => 0x0000,
+                EofPolicy::Halt | EofPolicy::Block => {
+                    diagnostic(
+                        Level::Warn,
+                        "Program requires more input than provided in the input file",
+                    );
+                    self.halt_reason = Some(HaltReason::OutOfInput);
+                    self.memory[CLK] = 0x0000;
+                    0x0000
+                }
+                EofPolicy::Sentinel(byte) => {
+                    self.memory[KBDR] = u16::from(byte);
+                    self.keyboard_ready = true;
+                    0x8000
+                }
+            },
+            _ => 0x0000,
+        }
+    }
+
+    /// Wait for a character from the keyboard, giving None if the machine stops while waiting.
+    /// Only someone at the keyboard can end the wait, so any other input gives None straight
+    /// away when it has nothing ready.
+    fn getc(&mut self) -> Option<u16> {
+        while self.poll_keyboard() == 0x0000 {
+            if !self.is_running() || !self.input.is_keyboard() {
+                return None;
+            }
+        }
+
+        self.keyboard_ready = false;
+        Some(self.memory[KBDR])
+    }
+
+    /// Display `text` as the operating system would, through the DDR
+    fn print(&mut self, text: &str) {
+        text.bytes()
+            .for_each(|byte| self.write(DDR as u16, u16::from(byte)));
+    }
+
+    /// Do what the standard operating system's routine for the trap `vector` does
+    fn builtin_trap(&mut self, vector: u16) {
+        match vector {
+            TRAP_GETC => match self.getc() {
+                Some(character) => self.registers[0] = character,
+                None => self.pc = self.pc_of_ir(),
+            },
+            TRAP_OUT => self.write(DDR as u16, self.registers[0] & 0xFF),
+            TRAP_PUTS => {
+                let mut address = self.registers[0];
+                while self.memory[address as usize] != 0x0000 {
+                    self.write(DDR as u16, self.memory[address as usize] & 0xFF);
+                    address = address.wrapping_add(1);
+                }
+            }
+            TRAP_IN => {
+                // Input which can't arrive while waiting is checked for first, so the prompt
+                // isn't shown again every time the TRAP is retried
+                if !self.input.is_keyboard() && self.poll_keyboard() == 0x0000 {
+                    self.pc = self.pc_of_ir();
+                    return;
+                }
+
+                self.print("\nInput a character> ");
+                match self.getc() {
+                    Some(character) => {
+                        self.write(DDR as u16, character);
+                        self.print("\n");
+                        self.registers[0] = character;
+                    }
+                    None => self.pc = self.pc_of_ir(),
+                }
+            }
+            TRAP_PUTSP => {
+                let mut address = self.registers[0];
+                'words: while self.memory[address as usize] != 0x0000 {
+                    let word = self.memory[address as usize];
+                    for character in [word & 0xFF, word >> 8] {
+                        if character == 0x0000 {
+                            break 'words;
+                        }
+                        self.write(DDR as u16, character);
+                    }
+                    address = address.wrapping_add(1);
+                }
+            }
+            TRAP_HALT => {
+                if !self.quiet_halt {
+                    self.print("\n----- Halting the processor ----- \n");
+                }
+                self.memory[CLK] = 0x0000;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Look for ESC in the input without the program asking for it, keeping anything else that
+    /// was typed for later.
+    fn check_for_interrupt(&mut self) {
+        let mut buf = [0; 1];
+        match self.input.read_now(&mut buf) {
+            Ok(x) if x != 0 => self.pending_input.push_back(buf[0]),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => self.interrupt(),
+            // Running out of input only matters once the program actually asks for more
+            _ => {}
+        }
+    }
+
+    fn interrupt(&mut self) {
+        diagnostic(Level::Info, "ESC pressed. Quitting simulator");
+        self.halt_reason = Some(HaltReason::UserInterrupt);
+        self.memory[CLK] = 0x0000;
+    }
+
+    /// Write to memory on behalf of a store instruction
+    fn store(&mut self, address: u16, value: u16) {
+        self.access_cache(address);
+
+        if self.check_devices {
+            match address as usize {
+                KBDR => self.device_warning("wrote to the KBDR, which is read only"),
+                KBSR if value & !0x4000 != 0 => {
+                    self.device_warning("set a bit of the KBSR other than the interrupt enable bit")
+                }
+                DSR if value & !0x4000 != 0 => {
+                    self.device_warning("set a bit of the DSR other than the interrupt enable bit")
+                }
+                _ => {}
+            }
+        }
+
+        if self.metadata.kind_of(address) == Some(RegionKind::Code) {
+            let message = format!(
+                "Instruction at {} stored to {}, which is marked as code",
+                self.formatter.hex(self.pc_of_ir()),
+                self.formatter.hex(address)
+            );
+            self.warn(self.pc_of_ir(), "store-to-code", message);
+        }
+
+        self.observe_access(
+            address,
+            WatchKind::Write,
+            self.memory[address as usize],
+            value,
+        );
+
+        if !(KBSR..=DDR).contains(&(address as usize)) {
+            if let Some(delta) = self.history.back_mut() {
+                delta.memory.push((address, self.memory[address as usize]));
+            }
+        }
+
+        self.original
+            .entry(address)
+            .or_insert(self.memory[address as usize]);
+        self.touched = Some(self.touched.map_or((address, address), |(low, high)| {
+            (low.min(address), high.max(address))
+        }));
+
+        self.write(address, value);
+
+        if self.check_next_store && address == self.pc {
+            self.fault(
+                "overwrote-next-instruction",
+                format!(
+                    "Store at {} overwrote the next instruction to execute, at {}",
+                    self.formatter.hex(self.pc_of_ir()),
+                    self.formatter.hex(address)
+                ),
+            );
+        }
+
+        if self.stack_canary == Some(address) {
+            self.fault(
+                "stack-canary",
+                format!(
+                    "{} at {} overwrote the stack canary at {}",
+                    Instruction::from(self.ir).to_assembly(&self.formatter),
+                    self.formatter.hex(self.pc_of_ir()),
+                    self.formatter.hex(address)
+                ),
+            );
+        }
+
+        if self.check_trap_table && self.pc_of_ir() >= USER_SPACE && self.in_trap_table(address) {
+            self.fault(
+                "trap-table-store",
+                format!(
+                    "User code at {} stored to {}, in the trap vector table",
+                    self.formatter.hex(self.pc_of_ir()),
+                    self.formatter.hex(address)
+                ),
+            );
+        }
+    }
+
+    /// Send a character to the display. If that fails the display is marked as not ready,
+    /// and the character is kept to be retried the next time the DSR is read.
+    fn output(&mut self, value: u8) {
+        if self.muted_until.is_some() {
+            return;
+        }
+
+        if let Some(ref mut held) = self.held_output {
+            held.push(value);
+            return;
+        }
+
+        let written = if value == b'\n' && !self.binary_output {
+            self.display.write_all(b"\r\n")
+        } else {
+            self.display.write_all(&[value])
+        };
+
+        match written {
+            Ok(()) => self.display_failures = 0,
+            Err(e) => {
+                self.memory[DSR] = 0x0000;
+                self.display_failures += 1;
+
+                if self.display_failures > self.display_retries {
+                    self.fault(
+                        "display-error",
+                        format!("Unable to write to the display: {}", e),
+                    );
+                } else {
+                    self.pending_output = Some(value);
+                }
+            }
+        }
+    }
+
+    /// Pass a data access through the cache, if there is one, and count it. Device registers
+    /// aren't cached or counted.
+    fn access_cache(&mut self, address: u16) {
+        if (address as usize) < DEVICE_REGISTERS {
+            if let Some(ref mut cache) = self.cache {
+                cache.access(address);
+            }
+            self.count_access(address);
+        }
+    }
+
+    fn count_access(&mut self, address: u16) {
+        if let Some(ref mut counts) = self.access_counts {
+            if let Some(count) = counts.get_mut(address as usize) {
+                *count += 1;
+            }
+        }
+    }
+
+    fn device_warning(&mut self, problem: &str) {
+        let message = format!(
+            "{} at {} {}",
+            Instruction::from(self.ir).to_assembly(&self.formatter),
+            self.formatter.hex(self.pc_of_ir()),
+            problem
+        );
+        self.warn(self.pc_of_ir(), "device-misuse", message);
+    }
+
+    /// Warn if an access relative to the stack pointer falls outside the stack guard
+    fn check_stack_access(&mut self, base: usize, address: u16) {
+        if let Some((start, end)) = self.stack_guard {
+            if base == self.stack_pointer && !(start..=end).contains(&address) {
+                let message = format!(
+                    "{} at {} accessed {}, outside the stack ({} to {})",
+                    Instruction::from(self.ir).to_assembly(&self.formatter),
+                    self.formatter.hex(self.pc_of_ir()),
+                    self.formatter.hex(address),
+                    self.formatter.hex(start),
+                    self.formatter.hex(end)
+                );
+                self.warn(self.pc_of_ir(), "outside-stack", message);
+            }
+        }
+    }
+
+    /// Record a warning, both as text and as a diagnostic
+    fn warn(&mut self, address: u16, code: &'static str, message: String) {
+        self.diagnostics.push(Diagnostic {
+            address,
+            severity: Severity::Warning,
+            code,
+            message: message.clone(),
+        });
+        self.warnings.push(message);
+    }
+
+    /// Raise the exception `vector`, pushing the PSR and PC onto the supervisor stack and
+    /// entering its handler in supervisor mode. With no handler in the interrupt vector table
+    /// the machine stops instead, as though it had faulted, and says why on the display.
+    fn exception(&mut self, vector: u16, code: &'static str, message: String) {
+        let handler = self.memory[INTERRUPT_TABLE.wrapping_add(vector) as usize];
+        if handler == 0x0000 {
+            format!("\n--- {} ---\n", message)
+                .bytes()
+                .for_each(|byte| self.output(byte));
+            self.fault(code, message);
+            return;
+        }
+
+        self.push_context();
+        self.pc = handler;
+    }
+
+    /// Push the PSR and PC onto the supervisor stack, switching to it from user mode first, so
+    /// that an RTI can later return to where the machine was
+    fn push_context(&mut self) {
+        let psr = self.psr;
+        if !self.privileged() {
+            self.saved_usp = self.registers[6];
+            self.registers[6] = self.saved_ssp;
+            self.psr &= !PSR_USER;
+        }
+
+        self.registers[6] = self.registers[6].wrapping_sub(1);
+        self.store(self.registers[6], psr);
+        self.registers[6] = self.registers[6].wrapping_sub(1);
+        self.store(self.registers[6], self.pc);
+    }
+
+    /// Stop the machine because something went wrong
+    fn fault(&mut self, code: &'static str, message: String) {
+        self.fault_context = Some((
+            self.pc_of_ir(),
+            Instruction::from(self.ir).to_assembly(&self.formatter),
+        ));
+        self.halt_reason = Some(HaltReason::Fault(message.clone()));
+        self.diagnostics.push(Diagnostic {
+            address: self.pc_of_ir(),
+            severity: Severity::Error,
+            code,
+            message: message.clone(),
+        });
+        self.warnings.push(message);
+        self.faulted = true;
+        self.memory[CLK] = 0x0000;
+    }
+
+    /// Count a call from the current subroutine to the one the PC now points at
+    fn record_call(&mut self) {
+        let caller = self
+            .call_entries
+            .last()
+            .copied()
+            .unwrap_or(self.entry_point);
+
+        *self.call_counts.entry((caller, self.pc)).or_insert(0) += 1;
+        self.call_entries.push(self.pc);
+    }
+
+    /// Update the branch predictor with the outcome of the conditional branch just executed
+    fn predict_branch(&mut self, outcome: Branch) {
+        let address = self.pc_of_ir();
+        let (predictor, total, mispredicted) =
+            self.predictors
+                .entry(address)
+                .or_insert((Predictor::default(), 0, 0));
+
+        let (before, after) = match self.predictor_kind {
+            PredictorKind::Static => (Predictor::StronglyNotTaken, Predictor::StronglyNotTaken),
+            PredictorKind::Bimodal => {
+                let before = *predictor;
+                *predictor = before.transition(outcome);
+                (before, *predictor)
+            }
+            PredictorKind::GShare { history_bits } => {
+                let mask = ((1u32 << history_bits) - 1) as u16;
+                let shared = &mut self.shared_predictors
+                    [usize::from((address ^ self.global_history) & mask)];
+                let before = *shared;
+                *shared = before.transition(outcome);
+
+                self.global_history =
+                    (self.global_history << 1 | u16::from(outcome == Branch::Taken)) & mask;
+                (before, *shared)
+            }
+        };
+
+        *total += 1;
+        if before.predict() != outcome {
+            *mispredicted += 1;
+        }
+
+        self.prediction = Some((before, after, outcome));
+    }
+
+    fn check_return(&mut self, returned_from: u16) {
+        if self.muted_until == Some(self.pc) {
+            self.muted_until = None;
+        }
+
+        self.call_entries.pop();
+
+        match self.call_stack.pop() {
+            Some(expected) if expected != self.pc && self.check_returns => {
+                let message = format!(
+                    "RET at {} returned to {}, but the matching call returns to {}",
+                    self.formatter.hex(returned_from),
+                    self.formatter.hex(self.pc),
+                    self.formatter.hex(expected)
+                );
+                self.warn(returned_from, "return-mismatch", message);
+            }
+            None if self.check_returns => {
+                let message = format!(
+                    "RET at {} has no matching JSR, JSRR, or TRAP",
+                    self.formatter.hex(returned_from)
+                );
+                self.warn(returned_from, "unmatched-return", message);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `address` is one of the entries of the trap vector table
+    fn in_trap_table(&self, address: u16) -> bool {
+        address.wrapping_sub(self.trap_base) < TRAP_TABLE_LENGTH
+    }
+
+    /// The address the instruction in the IR was fetched from
+    fn pc_of_ir(&self) -> u16 {
+        self.ir_address
+    }
+
+    fn evaluate(&mut self) {
         let opcode = self.ir & 0xF000;
 
         let destination_register = usize::from(self.ir >> 9 & 0b111);
@@ -191,10 +5770,32 @@ impl Simulator {
         let offset_6 = sign_extend(self.ir, 6);
         let imm5 = sign_extend(self.ir, 5);
 
+        if self.strict_decoding
+            && (opcode == OPCODE_ADD || opcode == OPCODE_AND)
+            && self.ir & 0x20 == 0
+            && self.ir & 0x18 != 0
+        {
+            let message = format!(
+                "{} at {} ({}) has bits 4 and 3 set in register mode",
+                Instruction::from(self.ir).to_assembly(&self.formatter),
+                self.formatter.hex(self.pc_of_ir()),
+                self.formatter.hex(self.ir)
+            );
+            self.warn(self.pc_of_ir(), "reserved-bits", message);
+        }
+
         match opcode {
             OPCODE_BR => {
-                if destination_register & self.cc != 0 {
-                    self.pc = (self.pc as i16 + pc_offset_9) as u16;
+                let taken = destination_register & usize::from(self.cc()) != 0;
+
+                // Only conditional branches are worth predicting
+                if destination_register != 0b000 && destination_register != 0b111 {
+                    self.predict_branch(Branch::from(taken));
+                }
+                self.last_branch = Some(Branch::from(taken));
+
+                if taken {
+                    self.pc = (self.pc as i16).wrapping_add(pc_offset_9) as u16;
                 }
             }
             OPCODE_ADD => {
@@ -207,28 +5808,28 @@ impl Simulator {
                 let result =
                     (self.registers[source_register_one] as i16).wrapping_add(source_two) as u16;
 
-                self.registers[destination_register] = result;
-                self.update_cc(result);
+                self.write_register(destination_register, result);
             }
             OPCODE_LD => {
-                let value = self.read((self.pc as i16 + pc_offset_9) as u16);
+                let value = self.read((self.pc as i16).wrapping_add(pc_offset_9) as u16);
 
-                self.registers[destination_register] = value;
-                self.update_cc(value);
+                self.write_register(destination_register, value);
             }
             OPCODE_ST => {
-                let address = (self.pc as i16 + pc_offset_9) as u16;
+                let address = (self.pc as i16).wrapping_add(pc_offset_9) as u16;
 
-                self.write(address, self.registers[destination_register]);
+                self.store(address, self.registers[destination_register]);
             }
             OPCODE_JSR => {
                 self.registers[7] = self.pc;
+                self.call_stack.push(self.pc);
 
                 self.pc = if self.ir & 0x0800 == 0 {
                     self.registers[source_register_one]
                 } else {
-                    (self.pc as i16 + sign_extend(self.ir, 11)) as u16
+                    (self.pc as i16).wrapping_add(sign_extend(self.ir, 11)) as u16
                 };
+                self.record_call();
             }
             OPCODE_AND => {
                 let source_two = if self.ir & 0x20 == 0 {
@@ -239,56 +5840,122 @@ impl Simulator {
 
                 let result = (self.registers[source_register_one] as i16 & source_two) as u16;
 
-                self.registers[destination_register] = result;
-                self.update_cc(result);
+                self.write_register(destination_register, result);
             }
             OPCODE_LDR => {
-                let value =
-                    self.read((self.registers[source_register_one] as i16 + offset_6) as u16);
+                let address =
+                    (self.registers[source_register_one] as i16).wrapping_add(offset_6) as u16;
+                self.check_stack_access(source_register_one, address);
+                let value = self.read(address);
 
-                self.registers[destination_register] = value;
-                self.update_cc(value);
+                self.write_register(destination_register, value);
             }
             OPCODE_STR => {
-                let address = (self.registers[source_register_one] as i16 + offset_6) as u16;
+                let address =
+                    (self.registers[source_register_one] as i16).wrapping_add(offset_6) as u16;
+                self.check_stack_access(source_register_one, address);
 
-                self.write(address, self.registers[destination_register]);
+                self.store(address, self.registers[destination_register]);
             }
             OPCODE_NOT => {
                 let value = !self.registers[source_register_one];
 
-                self.registers[destination_register] = value;
-                self.update_cc(value);
+                self.write_register(destination_register, value);
             }
             OPCODE_LDI => {
-                let indirect = self.read((self.pc as i16 + pc_offset_9) as u16);
+                let indirect = self.read((self.pc as i16).wrapping_add(pc_offset_9) as u16);
                 let value = self.read(indirect);
 
-                self.registers[destination_register] = value;
-                self.update_cc(value);
+                self.write_register(destination_register, value);
             }
             OPCODE_STI => {
-                let indirect = self.read((self.pc as i16 + pc_offset_9) as u16);
+                let indirect = self.read((self.pc as i16).wrapping_add(pc_offset_9) as u16);
 
-                self.write(indirect, self.registers[destination_register]);
+                self.store(indirect, self.registers[destination_register]);
             }
             OPCODE_JMP => {
+                let returned_from = self.pc_of_ir();
                 self.pc = self.registers[source_register_one];
+
+                if source_register_one == 7 {
+                    self.check_return(returned_from);
+                }
             }
             OPCODE_LEA => {
-                let address = (self.pc as i16 + pc_offset_9) as u16;
+                let address = (self.pc as i16).wrapping_add(pc_offset_9) as u16;
 
-                self.registers[destination_register] = address;
-                self.update_cc(address);
+                self.write_register(destination_register, address);
             }
             OPCODE_TRAP => {
                 self.registers[7] = self.pc;
 
-                let trap_vector = (self.ir & 0xFF) as usize;
-                self.pc = self.memory[trap_vector];
+                let trap_vector = self.ir & 0xFF;
+                let routine = self.memory[self.trap_base.wrapping_add(trap_vector) as usize];
+
+                if trap_vector == TRAP_PUTDEC && self.decimal_trap {
+                    format!("{}\n", self.registers[0] as i16)
+                        .bytes()
+                        .for_each(|byte| self.output(byte));
+                } else if self.builtin_traps
+                    && routine == 0x0000
+                    && (TRAP_GETC..=TRAP_HALT).contains(&trap_vector)
+                {
+                    self.builtin_trap(trap_vector);
+                } else {
+                    self.call_stack.push(self.pc);
+                    // A trap made from user mode runs on the supervisor stack, and returns with
+                    // an RTI
+                    if !self.privileged() {
+                        self.push_context();
+                    }
+                    if trap_vector == TRAP_HALT && self.quiet_halt {
+                        self.muted_until = Some(self.pc);
+                    }
+                    self.pc = routine;
+                    self.record_call();
+                }
             }
 
-            OPCODE_RTI | RESERVED => {}
+            RESERVED => match self.extensions.get(&(self.ir >> 3 & 0x7)).cloned() {
+                Some(handler) => handler(self, self.ir),
+                None => self.exception(
+                    ILLEGAL_OPCODE,
+                    "illegal-opcode",
+                    format!(
+                        "Illegal opcode {} at {}",
+                        self.formatter.hex(self.ir),
+                        self.formatter.hex(self.pc_of_ir())
+                    ),
+                ),
+            },
+            OPCODE_RTI => {
+                if self.privileged() {
+                    let returned_from = self.pc_of_ir();
+                    self.pc = self.read(self.registers[6]);
+                    let psr = self.read(self.registers[6].wrapping_add(1));
+                    self.registers[6] = self.registers[6].wrapping_add(2);
+                    self.psr = psr;
+
+                    if psr & PSR_USER != 0 {
+                        self.saved_ssp = self.registers[6];
+                        self.registers[6] = self.saved_usp;
+                    }
+
+                    // Returning from a trap made in user mode
+                    if self.call_stack.last() == Some(&self.pc) {
+                        self.check_return(returned_from);
+                    }
+                } else {
+                    self.exception(
+                        PRIVILEGE_VIOLATION,
+                        "privilege-violation",
+                        format!(
+                            "RTI at {} was executed in user mode",
+                            self.formatter.hex(self.pc_of_ir())
+                        ),
+                    );
+                }
+            }
             _ => unreachable!(),
         }
     }
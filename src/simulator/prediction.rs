@@ -0,0 +1,123 @@
+use std::fmt;
+
+/// Whether a conditional branch was (or is predicted to be) taken
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Branch {
+    Taken,
+    NotTaken,
+}
+
+impl From<bool> for Branch {
+    fn from(taken: bool) -> Self {
+        if taken {
+            Self::Taken
+        } else {
+            Self::NotTaken
+        }
+    }
+}
+
+impl fmt::Display for Branch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Branch::Taken => write!(f, "taken"),
+            Branch::NotTaken => write!(f, "not taken"),
+        }
+    }
+}
+
+/// A two bit saturating counter branch predictor.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::{Branch, Predictor};
+///
+/// let predictor = Predictor::default();
+/// assert_eq!(predictor.predict(), Branch::NotTaken);
+///
+/// // It takes two branches going the other way to change its mind from a strong prediction
+/// let predictor = predictor.transition(Branch::Taken);
+/// assert_eq!(predictor, Predictor::WeaklyTaken);
+/// let predictor = predictor.transition(Branch::Taken);
+/// assert_eq!(predictor, Predictor::StronglyTaken);
+/// let predictor = predictor.transition(Branch::NotTaken);
+/// assert_eq!(predictor.predict(), Branch::Taken);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Predictor {
+    StronglyNotTaken,
+    #[default]
+    WeaklyNotTaken,
+    WeaklyTaken,
+    StronglyTaken,
+}
+
+impl Predictor {
+    /// What the predictor expects the next branch to do
+    #[must_use]
+    pub fn predict(self) -> Branch {
+        match self {
+            Predictor::StronglyNotTaken | Predictor::WeaklyNotTaken => Branch::NotTaken,
+            Predictor::WeaklyTaken | Predictor::StronglyTaken => Branch::Taken,
+        }
+    }
+
+    /// The state of the predictor after seeing a branch go the way of `outcome`
+    #[must_use]
+    pub fn transition(self, outcome: Branch) -> Self {
+        match (self, outcome) {
+            (Predictor::StronglyNotTaken, Branch::Taken) => Predictor::WeaklyNotTaken,
+            (Predictor::WeaklyNotTaken, Branch::Taken) => Predictor::WeaklyTaken,
+            (Predictor::WeaklyTaken, Branch::Taken) => Predictor::StronglyTaken,
+            (Predictor::StronglyTaken, Branch::Taken) => Predictor::StronglyTaken,
+            (Predictor::StronglyNotTaken, Branch::NotTaken) => Predictor::StronglyNotTaken,
+            (Predictor::WeaklyNotTaken, Branch::NotTaken) => Predictor::StronglyNotTaken,
+            (Predictor::WeaklyTaken, Branch::NotTaken) => Predictor::WeaklyNotTaken,
+            (Predictor::StronglyTaken, Branch::NotTaken) => Predictor::WeaklyTaken,
+        }
+    }
+}
+
+impl fmt::Display for Predictor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Predictor::StronglyNotTaken => write!(f, "strongly not taken"),
+            Predictor::WeaklyNotTaken => write!(f, "weakly not taken"),
+            Predictor::WeaklyTaken => write!(f, "weakly taken"),
+            Predictor::StronglyTaken => write!(f, "strongly taken"),
+        }
+    }
+}
+
+/// Which branch predictor a simulator uses
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PredictorKind {
+    /// Always predict that a branch isn't taken
+    Static,
+    /// A two bit counter for each branch, kept by the branch's address
+    #[default]
+    Bimodal,
+    /// A table of `2^history_bits` two bit counters shared by every branch, indexed by the
+    /// branch's address XORed with the outcomes of the last `history_bits` branches
+    GShare { history_bits: u32 },
+}
+
+/// How well branches have been predicted so far
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PredictionStats {
+    /// How many conditional branches were executed
+    pub total: u64,
+    /// How many of those went the way they were predicted to
+    pub correct: u64,
+}
+
+impl PredictionStats {
+    /// The fraction of branches which were predicted correctly, or 0 if there haven't been any
+    #[must_use]
+    pub fn accuracy(&self) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => self.correct as f64 / total as f64,
+        }
+    }
+}
@@ -1,29 +1,209 @@
 use std::convert::From;
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, Error, ErrorKind, Read};
+use std::io::{BufReader, BufWriter, Cursor, Error, ErrorKind, Read, Write};
+use std::thread;
+use std::time::Duration;
 
-use crossterm::{input, InputEvent, KeyEvent, RawScreen, SyncReader};
+use crossterm::{input, AsyncReader, InputEvent, KeyEvent, RawScreen};
 
 /// An enum used to determine where to take input to the program from
 pub enum Reader {
-    Keyboard(Result<RawScreen, Error>, SyncReader),
+    Keyboard(Result<RawScreen, Error>, AsyncReader),
     InFile(BufReader<File>),
+    Buffer(Cursor<Vec<u8>>),
+    Recorder(Box<Reader>, BufWriter<File>),
+    /// Reads from the first reader until it runs out, and then from the second
+    Chain(Option<Box<Reader>>, Box<Reader>),
+    /// Input from anywhere else, taken to be someone typing, such as keys sent by a program
+    /// embedding the simulator. Reading nothing means no key has been pressed yet, and an
+    /// error stops the program as running out of input does.
+    Custom(Box<dyn Read>),
 }
 
+impl Reader {
+    /// Record every byte read from this reader into `file`, so that the session can later be
+    /// replayed by reading from that file.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::Cursor;
+    ///
+    /// // Echo every character read until the input runs out
+    /// let run = |input: Reader| {
+    ///     let mut simulator = Simulator::new(input, Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///         .load_source(
+    ///             0x3000,
+    ///             "LOOP    LDI R0, KBSR
+    ///                      BRzp LOOP
+    ///                      LDI R0, KBDR
+    ///                      STI R0, DDR
+    ///                      BRnzp LOOP
+    ///              KBSR    .FILL xFE00
+    ///              KBDR    .FILL xFE02
+    ///              DDR     .FILL xFE06",
+    ///         )
+    ///         .unwrap();
+    ///
+    ///     simulator.execute();
+    ///     simulator.display().contents().to_vec()
+    /// };
+    ///
+    /// let recording = std::env::temp_dir()
+    ///     .join(format!("lc3sim_recording_{}.txt", std::process::id()));
+    /// let recorder = Reader::Buffer(Cursor::new(b"hi".to_vec()))
+    ///     .record(recording.to_str().unwrap())
+    ///     .unwrap();
+    ///
+    /// let recorded = run(recorder);
+    /// let replayed = run(Reader::from(recording.to_str()));
+    ///
+    /// assert_eq!(recorded, b"hi");
+    /// assert_eq!(replayed, recorded);
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the recording file couldn't be created
+    pub fn record(self, file: &str) -> Result<Self, Error> {
+        Ok(Self::Recorder(
+            Box::new(self),
+            BufWriter::new(
+                OpenOptions::new()
+                    .write(true)
+                    .truncate(true)
+                    .create(true)
+                    .open(file)?,
+            ),
+        ))
+    }
+}
+
+impl Reader {
+    /// Carry on reading from `next` once this reader runs out, such as taking over at the
+    /// keyboard once a scripted prefix has been replayed from a file.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    /// use std::io::Cursor;
+    ///
+    /// // Something standing in for the keyboard
+    /// let keyboard = Reader::Buffer(Cursor::new(b"c".to_vec()));
+    /// let input = Reader::Buffer(Cursor::new(b"ab".to_vec())).then(keyboard);
+    ///
+    /// // Echo every character read until the input runs out
+    /// let mut simulator = Simulator::new(input, Writer::Buffer(Vec::new()), Tracer::NoTrace)
+    ///     .load_source(
+    ///         0x3000,
+    ///         "LOOP    LDI R0, KBSR
+    ///                  BRzp LOOP
+    ///                  LDI R0, KBDR
+    ///                  STI R0, DDR
+    ///                  BRnzp LOOP
+    ///          KBSR    .FILL xFE00
+    ///          KBDR    .FILL xFE02
+    ///          DDR     .FILL xFE06",
+    ///     )
+    ///     .unwrap();
+    ///
+    /// simulator.execute();
+    /// assert_eq!(simulator.display().contents(), b"abc");
+    /// ```
+    #[must_use]
+    pub fn then(self, next: Reader) -> Self {
+        Self::Chain(Some(Box::new(self)), Box::new(next))
+    }
+}
+
+impl Reader {
+    /// Whether the next byte read comes from someone typing at the keyboard, or a custom
+    /// reader standing in for them, rather than from a file or buffer.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Reader;
+    /// use std::io::empty;
+    ///
+    /// assert!(!Reader::from("hi").is_keyboard());
+    /// assert!(!Reader::from("hi").then(Reader::from("there")).is_keyboard());
+    /// assert!(Reader::Custom(Box::new(empty())).is_keyboard());
+    /// ```
+    pub fn is_keyboard(&self) -> bool {
+        match self {
+            Reader::Keyboard(..) | Reader::Custom(_) => true,
+            Reader::Recorder(ref reader, _) => reader.is_keyboard(),
+            Reader::Chain(Some(ref first), _) => first.is_keyboard(),
+            Reader::Chain(None, ref second) => second.is_keyboard(),
+            _ => false,
+        }
+    }
+}
+
+impl Reader {
+    /// Take input from `file`.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Reader;
+    /// use std::io::ErrorKind;
+    ///
+    /// let missing = std::env::temp_dir()
+    ///     .join(format!("lc3sim_reader_open_missing_{}.txt", std::process::id()));
+    /// let error = Reader::open(missing.to_str().unwrap()).err().unwrap();
+    /// assert_eq!(error.kind(), ErrorKind::NotFound);
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the file couldn't be opened
+    pub fn open(file: &str) -> Result<Self, Error> {
+        Ok(Self::InFile(BufReader::new(
+            OpenOptions::new().read(true).open(file)?,
+        )))
+    }
+}
+
+/// Input from a file, or from the keyboard if there's no file.
+///
+/// # Panics
+/// Will panic if the file couldn't be opened. Use [`Reader::open`](enum.Reader.html#method.open)
+/// to handle that instead.
 impl From<Option<&str>> for Reader {
     fn from(file: Option<&str>) -> Self {
-        file.map(|f| {
-            Self::InFile(BufReader::new(
-                OpenOptions::new().read(true).open(f).unwrap(),
-            ))
-        })
-        .unwrap_or_default()
+        file.map(|f| Self::open(f).unwrap()).unwrap_or_default()
+    }
+}
+
+/// Input taken from the bytes of `input`, such as keystrokes scripted for a test.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::Reader;
+/// use std::io::Read;
+///
+/// let mut reader = Reader::from("hi");
+/// let mut buf = [0; 1];
+/// assert_eq!(reader.read(&mut buf).unwrap(), 1);
+/// assert_eq!(buf, [b'h']);
+/// assert_eq!(reader.read(&mut buf).unwrap(), 1);
+/// assert_eq!(buf, [b'i']);
+/// // Running out behaves the same as running out of an input file
+/// assert!(reader.read(&mut buf).is_err());
+/// ```
+impl From<&str> for Reader {
+    fn from(input: &str) -> Self {
+        Self::from(input.as_bytes().to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Reader {
+    fn from(input: Vec<u8>) -> Self {
+        Self::Buffer(Cursor::new(input))
     }
 }
 
 impl Default for Reader {
     fn default() -> Self {
-        Self::Keyboard(RawScreen::into_raw_mode(), input().read_sync())
+        Self::Keyboard(RawScreen::into_raw_mode(), input().read_async())
     }
 }
 
@@ -48,8 +228,22 @@ impl Read for Reader {
     /// assert!(reader.read(&mut buf).is_err());
     /// ```
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let read = self.read_now(buf)?;
+        if read == 0 && self.is_keyboard() {
+            // Nothing has been typed yet, so avoid spinning flat out while a program waits for a key
+            thread::sleep(Duration::from_millis(1));
+        }
+        Ok(read)
+    }
+}
+
+impl Reader {
+    /// Read as `read` does, but return straight away when nothing has been typed yet, so that
+    /// the simulator can look for ESC between instructions without slowing the program down.
+    pub(crate) fn read_now(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
         match self {
-            // Input from the keyboard is gathered using crossterm
+            // Input from the keyboard is gathered using crossterm. Reading never blocks, so that the
+            // simulator can check for ESC while the program is busy doing something else.
             Reader::Keyboard(_, ref mut reader) => {
                 match reader.next() {
                     Some(InputEvent::Keyboard(KeyEvent::Char(key))) => {
@@ -73,7 +267,7 @@ impl Read for Reader {
                         Ok(1)
                     }
                     Some(InputEvent::Keyboard(KeyEvent::Esc)) => {
-                        // If the user hits the ESC key, then we want to exit. Of course, this only works if the program asks for input.
+                        // If the user hits the ESC key, then we want to exit.
                         Err(Error::new(ErrorKind::Interrupted, ""))
                     }
                     _ => {
@@ -91,6 +285,31 @@ impl Read for Reader {
                     _ => Err(Error::new(ErrorKind::NotFound, "")),
                 }
             }
+            // Input from a buffer behaves exactly like input from a file
+            Reader::Buffer(ref mut buffer) => match buffer.read(buf) {
+                Ok(x) if x > 0 => Ok(x),
+                _ => Err(Error::new(ErrorKind::NotFound, "")),
+            },
+            // Only bytes actually handed to the program are recorded. The recording is flushed
+            // straight away so that it survives the simulator being killed part way through.
+            Reader::Recorder(ref mut reader, ref mut recording) => {
+                let read = reader.read_now(buf)?;
+                recording.write_all(&buf[..read])?;
+                recording.flush()?;
+                Ok(read)
+            }
+            // Once the first reader runs out it's dropped, so it isn't asked again. Pressing
+            // ESC doesn't count as running out.
+            Reader::Chain(ref mut first, ref mut second) => {
+                if let Some(ref mut reader) = first {
+                    match reader.read_now(buf) {
+                        Err(ref e) if e.kind() != ErrorKind::Interrupted => *first = None,
+                        read => return read,
+                    }
+                }
+                second.read_now(buf)
+            }
+            Reader::Custom(ref mut reader) => reader.read(buf),
         }
     }
 }
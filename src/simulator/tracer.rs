@@ -3,15 +3,27 @@ use std::default::Default;
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Write};
 
+use serde::Serialize;
+
+#[derive(Default)]
 pub enum Tracer {
+    #[default]
     NoTrace,
     TraceFile(BufWriter<File>, u16, bool),
+    /// A trace file with a [`TraceRecord`](struct.TraceRecord.html) per instruction, as a line
+    /// of JSON
+    JsonTrace(BufWriter<File>, u16, bool),
+    /// A trace file with a row per instruction, after a header row written the first time
+    /// anything is traced into an empty file
+    Csv(BufWriter<File>, u16, bool),
+    /// Trace every instruction to somewhere other than a file
+    Custom(Box<dyn Write>),
 }
 
 impl From<(Option<&str>, Option<Vec<&str>>, bool)> for Tracer {
     fn from(args: (Option<&str>, Option<Vec<&str>>, bool)) -> Self {
         args.0
-            .and_then(|f| {
+            .map(|f| {
                 let trace_instructions = if let Some(instrs) = args.1 {
                     instrs.iter().fold(0, |acc, instr| {
                         acc | match instr.to_ascii_uppercase().as_ref() {
@@ -37,7 +49,7 @@ impl From<(Option<&str>, Option<Vec<&str>>, bool)> for Tracer {
                     0xFFFF
                 };
 
-                Some(Self::TraceFile(
+                Self::TraceFile(
                     BufWriter::new(
                         OpenOptions::new()
                             .write(true)
@@ -48,16 +60,138 @@ impl From<(Option<&str>, Option<Vec<&str>>, bool)> for Tracer {
                     ),
                     trace_instructions,
                     args.2,
-                ))
+                )
             })
             .unwrap_or_default()
     }
 }
 
-impl Default for Tracer {
-    fn default() -> Self {
-        Self::NoTrace
+impl Tracer {
+    /// Write each traced instruction to a trace file as a line of JSON, which can be read back
+    /// as a [`TraceRecord`](struct.TraceRecord.html). Anything other than a file is left as it
+    /// is.
+    #[must_use]
+    pub fn json(self) -> Self {
+        match self {
+            Tracer::TraceFile(file, want, userspace)
+            | Tracer::JsonTrace(file, want, userspace)
+            | Tracer::Csv(file, want, userspace) => Tracer::JsonTrace(file, want, userspace),
+            tracer => tracer,
+        }
     }
+
+    /// Write each traced instruction to a trace file as a row of its address, encoding,
+    /// disassembly, registers, and condition code, ready to be opened in a spreadsheet. Every
+    /// number is written in decimal, as in the JSON trace, so that it can be charted. Anything
+    /// other than a file is left as it is.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let trace = std::env::temp_dir().join(format!("lc3sim_trace_{}.csv", std::process::id()));
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::from((trace.to_str(), Some(vec!["ADD"]), false)).csv(),
+    /// )
+    /// .load_source(
+    ///     0x3000,
+    ///     "        ADD R1, R1, #1
+    ///              ADD R1, R1, #-1
+    ///              STI R1, MCR
+    ///      MCR     .FILL xFFFE",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    /// drop(simulator);
+    ///
+    /// assert_eq!(
+    ///     std::fs::read_to_string(trace).unwrap(),
+    ///     "pc,ir,disasm,r0,r1,r2,r3,r4,r5,r6,r7,cc\n\
+    ///      12288,4705,\"ADD R1, R1, #1\",0,1,0,0,0,0,0,0,P\n\
+    ///      12289,4735,\"ADD R1, R1, #-1\",0,0,0,0,0,0,0,0,Z\n"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn csv(self) -> Self {
+        match self {
+            Tracer::TraceFile(file, want, userspace)
+            | Tracer::JsonTrace(file, want, userspace)
+            | Tracer::Csv(file, want, userspace) => Tracer::Csv(file, want, userspace),
+            tracer => tracer,
+        }
+    }
+}
+
+/// How each traced instruction is written to a text trace
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TraceFormat {
+    /// Every register, the PC, and the condition code after each instruction
+    #[default]
+    Full,
+    /// A single line per instruction with its address, encoding, disassembly, and any
+    /// registers it changed
+    Pretty,
+}
+
+/// A traced instruction, along with the state of the machine after it executed. Each tracer
+/// writes it out in its own way.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+///
+/// let trace = std::env::temp_dir().join(format!("lc3sim_trace_{}.json", std::process::id()));
+/// let mut simulator = Simulator::new(
+///     Reader::from(""),
+///     Writer::Buffer(Vec::new()),
+///     Tracer::from((trace.to_str(), None, false)).json(),
+/// )
+/// .load_source(
+///     0x3000,
+///     "        ADD R1, R1, #1
+///              STI R2, MCR
+///      MCR     .FILL xFFFE",
+/// )
+/// .unwrap();
+///
+/// simulator.execute();
+/// drop(simulator);
+///
+/// let trace = std::fs::read_to_string(trace).unwrap();
+/// let records = trace
+///     .lines()
+///     .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+///     .collect::<Vec<_>>();
+///
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(
+///     records[0],
+///     serde_json::json!({
+///         "pc": 0x3000,
+///         "ir": 0x1261,
+///         "disasm": "ADD R1, R1, #1",
+///         "registers": [0, 1, 0, 0, 0, 0, 0, 0],
+///         "cc": "P",
+///     })
+/// );
+/// assert_eq!(records[1]["disasm"], "STI R2, #0");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TraceRecord {
+    /// The address the instruction was fetched from
+    pub pc: u16,
+    pub ir: u16,
+    pub disasm: String,
+    pub registers: [u16; 8],
+    /// The condition code as one of 'N', 'Z', or 'P'
+    pub cc: char,
+    /// The instruction as it's written to a text trace, in the simulator's
+    /// [`TraceFormat`](enum.TraceFormat.html)
+    #[serde(skip)]
+    pub text: String,
 }
 
 /// A trait meant for implementing the tracing ability of a tracer
@@ -65,25 +199,73 @@ pub trait Trace {
     /// Whether or not the tracer wants to trace the instruction
     fn wants(&self, instruction: u16, pc: u16) -> bool;
     /// The specific implementation of the trace
-    fn trace(&mut self, string: &str);
+    fn trace(&mut self, record: &TraceRecord);
+    /// Trace something other than an instruction, such as a memory access or a branch
+    /// prediction. Traces meant to be read by another program leave these out.
+    fn note(&mut self, text: &str);
 }
 
 impl Trace for Tracer {
     fn wants(&self, instruction: u16, pc: u16) -> bool {
         match self {
             Tracer::NoTrace => false,
-            Tracer::TraceFile(_, want, userspace) => {
+            Tracer::TraceFile(_, want, userspace)
+            | Tracer::JsonTrace(_, want, userspace)
+            | Tracer::Csv(_, want, userspace) => {
                 (!userspace || pc >= 0x3000) && (want & (1 << instruction)) != 0
             }
+            Tracer::Custom(_) => true,
         }
     }
 
-    fn trace(&mut self, string: &str) {
+    fn trace(&mut self, record: &TraceRecord) {
         match self {
             Tracer::NoTrace => {}
-            Tracer::TraceFile(ref mut file, _, _) => match write!(file, "{}", string) {
-                _ => {}
-            },
+            Tracer::TraceFile(ref mut file, _, _) => {
+                let _ = write!(file, "{}", record.text);
+            }
+            Tracer::JsonTrace(ref mut file, _, _) => {
+                let _ = writeln!(file, "{}", serde_json::to_string(record).unwrap());
+            }
+            Tracer::Csv(ref mut file, _, _) => {
+                // Nothing has been traced yet if neither the buffer nor the file has anything
+                // in it
+                if file.buffer().is_empty()
+                    && file.get_ref().metadata().is_ok_and(|file| file.len() == 0)
+                {
+                    let _ = writeln!(file, "pc,ir,disasm,r0,r1,r2,r3,r4,r5,r6,r7,cc");
+                }
+
+                let _ = writeln!(
+                    file,
+                    "{},{},\"{}\",{},{}",
+                    record.pc,
+                    record.ir,
+                    record.disasm,
+                    record
+                        .registers
+                        .iter()
+                        .map(u16::to_string)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    record.cc
+                );
+            }
+            Tracer::Custom(ref mut writer) => {
+                let _ = writer.write_all(record.text.as_bytes());
+            }
+        }
+    }
+
+    fn note(&mut self, text: &str) {
+        match self {
+            Tracer::TraceFile(ref mut file, _, _) => {
+                let _ = write!(file, "{}", text);
+            }
+            Tracer::Custom(ref mut writer) => {
+                let _ = writer.write_all(text.as_bytes());
+            }
+            _ => {}
         }
     }
 }
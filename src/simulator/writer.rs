@@ -3,28 +3,125 @@ use std::default::Default;
 use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Result, Write};
 
-use std::str;
-
 use crossterm::terminal;
 
+/// An enum used to determine where to send output from the program to
 pub enum Writer {
     Terminal(crossterm::Terminal),
     OutFile(BufWriter<File>),
+    /// A file which is flushed on every newline, and synced to disk as well if the flag is set
+    LineBuffered(BufWriter<File>, bool),
+    Buffer(Vec<u8>),
+    Custom(Box<dyn Write>),
 }
 
+/// Output to a file, or to the terminal if there's no file.
+///
+/// # Panics
+/// Will panic if the file couldn't be created. Use [`Writer::create`](enum.Writer.html#method.create)
+/// to handle that instead.
 impl From<Option<&str>> for Writer {
     fn from(file: Option<&str>) -> Self {
-        file.map(|f| {
-            Self::OutFile(BufWriter::new(
-                OpenOptions::new()
-                    .write(true)
-                    .truncate(true)
-                    .create(true)
-                    .open(f)
-                    .unwrap(),
-            ))
-        })
-        .unwrap_or_default()
+        file.map(|f| Self::create(f).unwrap()).unwrap_or_default()
+    }
+}
+
+impl Writer {
+    /// Send output to `file`, replacing anything which was already in it. Output is buffered
+    /// until the writer is flushed or dropped.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Writer;
+    /// use std::io::Write;
+    ///
+    /// let directory = std::env::temp_dir();
+    /// assert!(Writer::create(directory.to_str().unwrap()).is_err());
+    ///
+    /// let file = directory.join(format!("lc3sim_create_{}.txt", std::process::id()));
+    /// let mut writer = Writer::create(file.to_str().unwrap()).unwrap();
+    /// writer.write_all(b"hi").unwrap();
+    /// assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+    /// writer.flush().unwrap();
+    /// assert_eq!(std::fs::read_to_string(&file).unwrap(), "hi");
+    /// ```
+    ///
+    /// # Errors
+    /// Will return Err if the file couldn't be created
+    pub fn create(file: &str) -> Result<Self> {
+        Ok(Self::OutFile(BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .create(true)
+                .open(file)?,
+        )))
+    }
+
+    /// Flush an output file on every newline (and sync it to disk as well if `sync` is set),
+    /// so that anything watching the file sees each line as soon as it's written. Anything
+    /// other than a file is left as it is.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::Writer;
+    /// use std::io::Write;
+    ///
+    /// let file = std::env::temp_dir()
+    ///     .join(format!("lc3sim_line_buffered_{}.txt", std::process::id()));
+    /// let file = file.to_str().unwrap();
+    /// let mut writer = Writer::from(Some(file)).line_buffered(false);
+    ///
+    /// writer.write_all(b"first\n").unwrap();
+    /// assert_eq!(std::fs::read_to_string(file).unwrap(), "first\n");
+    /// writer.write_all(b"second\n").unwrap();
+    /// assert_eq!(std::fs::read_to_string(file).unwrap(), "first\nsecond\n");
+    /// ```
+    #[must_use]
+    pub fn line_buffered(self, sync: bool) -> Self {
+        match self {
+            Writer::OutFile(file) | Writer::LineBuffered(file, _) => {
+                Writer::LineBuffered(file, sync)
+            }
+            writer => writer,
+        }
+    }
+
+    /// Everything written to a buffer so far, or nothing for any other kind of writer.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+    ///
+    /// let mut simulator = Simulator::new(
+    ///     Reader::from(""),
+    ///     Writer::Buffer(Vec::new()),
+    ///     Tracer::NoTrace,
+    /// )
+    /// .load_source(
+    ///     0x3000,
+    ///     "        LEA R1, HELLO
+    ///      LOOP    LDR R0, R1, #0
+    ///              BRz DONE
+    ///              STI R0, DDR
+    ///              ADD R1, R1, #1
+    ///              BRnzp LOOP
+    ///      DONE    STI R0, MCR
+    ///      DDR     .FILL xFE06
+    ///      MCR     .FILL xFFFE
+    ///      HELLO   .STRINGZ \"Hello World!\"",
+    /// )
+    /// .unwrap();
+    ///
+    /// simulator.execute();
+    /// assert_eq!(simulator.display().contents(), b"Hello World!");
+    /// ```
+    #[must_use]
+    pub fn contents(&self) -> &[u8] {
+        match self {
+            Writer::Buffer(buffer) => buffer,
+            _ => &[],
+        }
     }
 }
 
@@ -34,22 +131,75 @@ impl Default for Writer {
     }
 }
 
+/// Whatever bytes the simulator sends are passed along as they are, whether or not they're
+/// valid UTF-8. The only change the simulator makes to a program's output is a carriage
+/// return before each newline, unless
+/// [`with_binary_output`](struct.Simulator.html#method.with_binary_output) is set. Only the
+/// terminal needs text, and it shows anything else as a replacement character.
+///
+/// # Examples
+/// ```
+/// use lc3simlib::simulator::{Reader, Simulator, Tracer, Writer};
+///
+/// let file = std::env::temp_dir()
+///     .join(format!("lc3sim_binary_output_{}.txt", std::process::id()));
+/// let mut simulator = Simulator::new(
+///     Reader::from(""),
+///     Writer::from(file.to_str()),
+///     Tracer::NoTrace,
+/// )
+/// .load_source(
+///     0x3000,
+///     "        LD R0, BYTE
+///              STI R0, DDR
+///              AND R0, R0, #0
+///              ADD R0, R0, #10
+///              STI R0, DDR
+///              AND R0, R0, #0
+///              STI R0, MCR
+///      BYTE    .FILL xFF
+///      DDR     .FILL xFE06
+///      MCR     .FILL xFFFE",
+/// )
+/// .unwrap();
+///
+/// simulator.execute();
+/// drop(simulator);
+///
+/// assert_eq!(std::fs::read(file).unwrap(), [0xFF, b'\r', b'\n']);
+/// ```
 impl Write for Writer {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let s = str::from_utf8(&buf).unwrap();
         match self {
-            Writer::Terminal(ref mut terminal) => match terminal.write(s) {
-                _ => {}
-            },
-            Writer::OutFile(ref mut file) => match write!(file, "{}", s) {
-                _ => {}
-            },
+            // The terminal only takes text, so anything which isn't valid UTF-8 is shown as a
+            // replacement character rather than stopping the simulator
+            Writer::Terminal(ref mut terminal) => {
+                terminal.write(String::from_utf8_lossy(buf))?;
+            }
+            Writer::OutFile(ref mut file) => file.write_all(buf)?,
+            Writer::LineBuffered(ref mut file, sync) => {
+                file.write_all(buf)?;
+                if buf.contains(&b'\n') {
+                    file.flush()?;
+                    if *sync {
+                        file.get_ref().sync_data()?;
+                    }
+                }
+            }
+            // Output kept in memory is mostly useful for tests, or for embedding the simulator,
+            // so it's passed along exactly as the program wrote it
+            Writer::Buffer(ref mut buffer) => buffer.extend_from_slice(buf),
+            Writer::Custom(ref mut writer) => writer.write_all(buf)?,
         }
 
-        Ok(s.len())
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> Result<()> {
-        Ok(())
+        match self {
+            Writer::OutFile(ref mut file) | Writer::LineBuffered(ref mut file, _) => file.flush(),
+            Writer::Custom(ref mut writer) => writer.flush(),
+            _ => Ok(()),
+        }
     }
 }
@@ -0,0 +1,245 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{Error, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crossterm::{cursor, terminal, ClearType, Terminal};
+
+/// The fewest columns the terminal can have for the split view to be used
+pub const MINIMUM_COLUMNS: u16 = 40;
+
+/// The fewest rows left over for program output once the trace lines and separator are drawn
+pub const MINIMUM_OUTPUT_ROWS: u16 = 4;
+
+/// How long to wait between redraws, so that drawing doesn't slow the simulator down
+const REDRAW_INTERVAL: Duration = Duration::from_millis(30);
+
+/// Shows the program's output in the top of the terminal, and the last few lines of the trace
+/// underneath it.
+///
+/// The terminal needs to be at least [`MINIMUM_COLUMNS`](constant.MINIMUM_COLUMNS.html)
+/// wide, and tall enough for the trace lines, a separator, and
+/// [`MINIMUM_OUTPUT_ROWS`](constant.MINIMUM_OUTPUT_ROWS.html) rows of output. Lines longer
+/// than the terminal is wide are cut short.
+///
+/// # Examples
+/// ```no_run
+/// use lc3simlib::simulator::{Reader, Simulator, TraceFormat, Tracer, Writer};
+/// use lc3simlib::split_view::SplitView;
+///
+/// let view = SplitView::new(8).expect("The terminal is too small for the split view");
+/// let mut simulator = Simulator::new(
+///     Reader::default(),
+///     Writer::Custom(Box::new(view.output())),
+///     Tracer::Custom(Box::new(view.trace())),
+/// )
+/// .with_trace_format(TraceFormat::Pretty)
+/// .with_operating_system("./LC3_OS.obj");
+///
+/// simulator.execute();
+/// view.draw().unwrap();
+/// ```
+pub struct SplitView {
+    view: Rc<RefCell<View>>,
+}
+
+impl SplitView {
+    /// A split view showing `trace_lines` (at least 1) lines of the trace, or None if the
+    /// terminal is too small for it.
+    #[must_use]
+    pub fn new(trace_lines: u16) -> Option<Self> {
+        let terminal = terminal();
+        let (columns, rows) = terminal.terminal_size();
+
+        Self::build(Some(terminal), columns, rows, trace_lines)
+    }
+
+    /// A split view of a screen `columns` wide and `rows` tall which is never drawn to the
+    /// terminal, only kept for [`screen`](#method.screen), or None if it's too small.
+    ///
+    /// # Examples
+    /// ```
+    /// use lc3simlib::split_view::SplitView;
+    /// use std::io::Write;
+    ///
+    /// let view = SplitView::with_size(40, 8, 2).unwrap();
+    /// view.output().write_all(b"first\nsecond\n").unwrap();
+    /// view.output().write_all(&[b'x'; 50]).unwrap();
+    /// view.trace().write_all(b"one\ntwo\nthree\n").unwrap();
+    ///
+    /// let mut expected = vec![String::from("first"), String::from("second"), "x".repeat(40)];
+    /// expected.extend(vec![String::new(); 2]);
+    /// expected.push("-".repeat(40));
+    /// expected.extend(vec![String::from("two"), String::from("three")]);
+    /// assert_eq!(view.screen(), expected);
+    ///
+    /// assert!(SplitView::with_size(39, 8, 2).is_none());
+    /// assert!(SplitView::with_size(40, 6, 2).is_none());
+    /// ```
+    #[must_use]
+    pub fn with_size(columns: u16, rows: u16, trace_lines: u16) -> Option<Self> {
+        Self::build(None, columns, rows, trace_lines)
+    }
+
+    fn build(
+        terminal: Option<Terminal>,
+        columns: u16,
+        rows: u16,
+        trace_lines: u16,
+    ) -> Option<Self> {
+        let trace_lines = trace_lines.max(1);
+
+        if columns < MINIMUM_COLUMNS || rows < trace_lines + 1 + MINIMUM_OUTPUT_ROWS {
+            return None;
+        }
+
+        Some(Self {
+            view: Rc::new(RefCell::new(View {
+                terminal,
+                columns,
+                output_rows: rows - trace_lines - 1,
+                output: vec![String::new()],
+                trace_lines,
+                trace: VecDeque::with_capacity(usize::from(trace_lines)),
+                drawn: None,
+            })),
+        })
+    }
+
+    /// Somewhere to write the program's output to
+    #[must_use]
+    pub fn output(&self) -> Output {
+        Output(Rc::clone(&self.view))
+    }
+
+    /// Somewhere to write the trace to
+    #[must_use]
+    pub fn trace(&self) -> Trace {
+        Trace(Rc::clone(&self.view))
+    }
+
+    /// Redraw the terminal straight away, such as once the program has finished.
+    ///
+    /// # Errors
+    /// Will return Err if the terminal couldn't be drawn to
+    pub fn draw(&self) -> Result<(), Error> {
+        self.view.borrow_mut().draw()
+    }
+
+    /// Every row of the screen as it's drawn: the program's output, a separator, and then the
+    /// trace, each cut short to fit the width of the screen.
+    #[must_use]
+    pub fn screen(&self) -> Vec<String> {
+        self.view.borrow().screen()
+    }
+}
+
+/// The program output half of a [`SplitView`](struct.SplitView.html)
+pub struct Output(Rc<RefCell<View>>);
+
+/// The trace half of a [`SplitView`](struct.SplitView.html)
+pub struct Trace(Rc<RefCell<View>>);
+
+struct View {
+    /// Where the view is drawn, or None if it's only kept in memory
+    terminal: Option<Terminal>,
+    columns: u16,
+    output_rows: u16,
+    output: Vec<String>,
+    trace_lines: u16,
+    trace: VecDeque<String>,
+    drawn: Option<Instant>,
+}
+
+impl View {
+    fn redraw(&mut self) -> Result<(), Error> {
+        if self
+            .drawn
+            .is_none_or(|drawn| drawn.elapsed() >= REDRAW_INTERVAL)
+        {
+            self.draw()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn draw(&mut self) -> Result<(), Error> {
+        if let Some(ref terminal) = self.terminal {
+            let cursor = cursor();
+
+            terminal.clear(ClearType::All)?;
+
+            for (row, line) in self.screen().into_iter().enumerate() {
+                cursor.goto(0, row as u16)?;
+                terminal.write(line)?;
+            }
+        }
+
+        self.drawn = Some(Instant::now());
+        Ok(())
+    }
+
+    fn screen(&self) -> Vec<String> {
+        let columns = usize::from(self.columns);
+        let fit = |line: &String| line.chars().take(columns).collect::<String>();
+
+        let mut screen = self.output.iter().map(fit).collect::<Vec<_>>();
+        screen.resize(usize::from(self.output_rows), String::new());
+        screen.push("-".repeat(columns));
+        screen.extend(self.trace.iter().map(fit));
+        screen.resize(
+            usize::from(self.output_rows + 1 + self.trace_lines),
+            String::new(),
+        );
+        screen
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let mut view = self.0.borrow_mut();
+
+        for c in String::from_utf8_lossy(buf).chars() {
+            match c {
+                '\r' => {}
+                '\n' => view.output.push(String::new()),
+                c => view.output.last_mut().unwrap().push(c),
+            }
+        }
+
+        // Only what fits on screen needs to be kept
+        let excess = view
+            .output
+            .len()
+            .saturating_sub(usize::from(view.output_rows));
+        view.output.drain(..excess);
+
+        view.redraw()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.0.borrow_mut().draw()
+    }
+}
+
+impl Write for Trace {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let mut view = self.0.borrow_mut();
+
+        for line in String::from_utf8_lossy(buf).lines() {
+            if view.trace.len() == usize::from(view.trace_lines) {
+                view.trace.pop_front();
+            }
+            view.trace.push_back(String::from(line));
+        }
+
+        view.redraw()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.0.borrow_mut().draw()
+    }
+}